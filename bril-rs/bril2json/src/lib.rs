@@ -8,19 +8,21 @@
 pub mod bril_grammar;
 #[doc(hidden)]
 pub mod cli;
-use bril_rs::{AbstractProgram, Position};
+use bril_rs::{AbstractProgram, Literal, Position};
 
 #[doc(hidden)]
 #[derive(Clone)]
 pub struct Lines {
     use_pos: bool,
+    wrap_int_literals: bool,
     new_lines: Vec<usize>,
 }
 
 impl Lines {
-    fn new(input: &str, use_pos: bool) -> Self {
+    fn new(input: &str, use_pos: bool, wrap_int_literals: bool) -> Self {
         Self {
             use_pos,
+            wrap_int_literals,
             new_lines: input
                 .as_bytes()
                 .iter()
@@ -30,10 +32,35 @@ impl Lines {
         }
     }
 
+    // An integer literal that does not fit in an `i64` either wraps around into it or becomes the nearest float, which is also what parsing it from JSON gives
+    fn int_literal(&self, s: &str) -> Literal {
+        match s.parse::<i64>() {
+            Ok(i) => Literal::Int(i),
+            Err(_) if self.wrap_int_literals => {
+                let magnitude = s
+                    .trim_start_matches(['+', '-'])
+                    .bytes()
+                    .fold(0_i64, |acc, d| {
+                        acc.wrapping_mul(10).wrapping_add(i64::from(d - b'0'))
+                    });
+                Literal::Int(if s.starts_with('-') {
+                    magnitude.wrapping_neg()
+                } else {
+                    magnitude
+                })
+            }
+            Err(_) => Literal::Float(s.parse().unwrap()),
+        }
+    }
+
     fn get_position(&self, index: usize) -> Option<Position> {
         if self.use_pos {
+            // Anything before the first newline is on the first line
             Some(self.new_lines.iter().enumerate().fold(
-                Position { col: 1, row: 0 },
+                Position {
+                    col: (index + 1) as u64,
+                    row: 1,
+                },
                 |current, (line_num, idx)| {
                     if *idx < index {
                         Position {
@@ -62,7 +89,22 @@ pub fn parse_abstract_program_from_read<R: std::io::Read>(
     input.read_to_string(&mut buffer).unwrap();
     let parser = bril_grammar::AbstractProgramParser::new();
     parser
-        .parse(&Lines::new(&buffer, use_pos), &buffer)
+        .parse(&Lines::new(&buffer, use_pos, false), &buffer)
+        .unwrap()
+}
+
+/// The same as [`parse_abstract_program_from_read`] except that an integer literal outside of the range of an `i64` wraps around into it using two's complement, i.e. `9223372036854775808` becomes `-9223372036854775808`. Otherwise it is parsed as the nearest float like it would be from JSON.
+/// # Panics
+/// Will panic if the input is not well-formed Bril text
+pub fn parse_abstract_program_from_read_wrapping<R: std::io::Read>(
+    mut input: R,
+    use_pos: bool,
+) -> AbstractProgram {
+    let mut buffer = String::new();
+    input.read_to_string(&mut buffer).unwrap();
+    let parser = bril_grammar::AbstractProgramParser::new();
+    parser
+        .parse(&Lines::new(&buffer, use_pos, true), &buffer)
         .unwrap()
 }
 
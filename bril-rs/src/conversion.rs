@@ -217,6 +217,18 @@ impl TryFrom<AbstractInstruction> for Instruction {
                     "or" => ValueOps::Or,
                     "call" => ValueOps::Call,
                     "id" => ValueOps::Id,
+                    "dump" => ValueOps::Dump,
+                    "gcd" => ValueOps::Gcd,
+                    "lcm" => ValueOps::Lcm,
+                    "rotl" => ValueOps::Rotl,
+                    "rotr" => ValueOps::Rotr,
+                    "parity" => ValueOps::Parity,
+                    "undef" => ValueOps::Undef,
+                    "bool2int" => ValueOps::Bool2int,
+                    "int2bool" => ValueOps::Int2bool,
+                    "clock" => ValueOps::Clock,
+                    "argc" => ValueOps::Argc,
+                    "unique_id" => ValueOps::UniqueId,
                     "sub" => ValueOps::Sub,
                     #[cfg(feature = "ssa")]
                     "phi" => ValueOps::Phi,
@@ -238,12 +250,30 @@ impl TryFrom<AbstractInstruction> for Instruction {
                     "fle" => ValueOps::Fle,
                     #[cfg(feature = "float")]
                     "fge" => ValueOps::Fge,
+                    #[cfg(feature = "float")]
+                    "fapproxeq" => ValueOps::Fapproxeq,
+                    #[cfg(feature = "float")]
+                    "fapprox" => ValueOps::Fapprox,
+                    #[cfg(feature = "float")]
+                    "fadd_kahan" => ValueOps::FaddKahan,
                     #[cfg(feature = "memory")]
                     "alloc" => ValueOps::Alloc,
                     #[cfg(feature = "memory")]
                     "load" => ValueOps::Load,
                     #[cfg(feature = "memory")]
                     "ptradd" => ValueOps::PtrAdd,
+                    #[cfg(feature = "memory")]
+                    "samebase" => ValueOps::Samebase,
+                    #[cfg(feature = "memory")]
+                    "ptrdiff" => ValueOps::Ptrdiff,
+                    #[cfg(feature = "memory")]
+                    "heapblocks" => ValueOps::Heapblocks,
+                    #[cfg(feature = "memory")]
+                    "alloca" => ValueOps::Alloca,
+                    #[cfg(feature = "memory")]
+                    "dup" => ValueOps::Dup,
+                    #[cfg(feature = "memory")]
+                    "memdup" => ValueOps::Memdup,
                     v => {
                         return Err(ConversionError::InvalidValueOps(v.to_string()))
                             .map_err(|e| e.add_pos(pos))
@@ -270,6 +300,8 @@ impl TryFrom<AbstractInstruction> for Instruction {
                     "ret" => EffectOps::Return,
                     "print" => EffectOps::Print,
                     "nop" => EffectOps::Nop,
+                    "debugbreak" => EffectOps::Debugbreak,
+                    "fence" => EffectOps::Fence,
                     #[cfg(feature = "memory")]
                     "store" => EffectOps::Store,
                     #[cfg(feature = "memory")]
@@ -283,6 +283,10 @@ pub enum EffectOps {
     Print,
     /// <https://capra.cs.cornell.edu/bril/lang/core.html#miscellaneous>
     Nop,
+    /// An extension which pauses execution and hands the current state to a debugger, if one is attached
+    Debugbreak,
+    /// An extension which orders memory operations for a future concurrency extension. Programs only have one thread for now so it does nothing
+    Fence,
     /// <https://capra.cs.cornell.edu/bril/lang/memory.html#operations>
     #[cfg(feature = "memory")]
     Store,
@@ -309,6 +313,8 @@ impl Display for EffectOps {
             EffectOps::Return => write!(f, "ret"),
             EffectOps::Print => write!(f, "print"),
             EffectOps::Nop => write!(f, "nop"),
+            EffectOps::Debugbreak => write!(f, "debugbreak"),
+            EffectOps::Fence => write!(f, "fence"),
             #[cfg(feature = "memory")]
             EffectOps::Store => write!(f, "store"),
             #[cfg(feature = "memory")]
@@ -355,6 +361,31 @@ pub enum ValueOps {
     Call,
     /// <https://capra.cs.cornell.edu/bril/lang/core.html#miscellaneous>
     Id,
+    /// A debugging extension which acts like [`ValueOps::Id`] but also prints its argument under the instruction's label
+    Dump,
+    /// An extension which computes the greatest common divisor of the magnitudes of two integers
+    Gcd,
+    /// An extension which computes the least common multiple of the magnitudes of two integers
+    Lcm,
+    /// An extension which rotates the bits of an integer to the left by an amount taken modulo 64
+    Rotl,
+    /// An extension which rotates the bits of an integer to the right by an amount taken modulo 64
+    Rotr,
+    /// An extension which computes the parity of an integer: `1` if an odd number of its bits are set and `0` otherwise
+    Parity,
+    /// An extension which produces a deliberately undefined value of any type
+    Undef,
+    /// An extension which converts `true` to `1` and `false` to `0`
+    Bool2int,
+    /// An extension which converts `0` to `false` and any other `int`, including negative ones, to `true`
+    Int2bool,
+    /// An extension which reads a logical clock: the number of instructions that have been executed so far
+    Clock,
+    /// An extension which returns the number of arguments that the function being executed was called with
+    Argc,
+    /// An extension which returns a different integer every time that it is executed, counting up from `0`
+    #[serde(rename = "unique_id")]
+    UniqueId,
     /// <https://capra.cs.cornell.edu/bril/lang/ssa.html#operations>
     #[cfg(feature = "ssa")]
     Phi,
@@ -385,6 +416,16 @@ pub enum ValueOps {
     /// <https://capra.cs.cornell.edu/bril/lang/float.html#operations>
     #[cfg(feature = "float")]
     Fge,
+    /// An extension which checks whether two floats are within an absolute tolerance of each other
+    #[cfg(feature = "float")]
+    Fapproxeq,
+    /// The same as `fapproxeq`, under the name used by some float benchmarks
+    #[cfg(feature = "float")]
+    Fapprox,
+    /// An extension for compensated summation. `fadd_kahan sum c x` returns `sum + x` and also updates the variable `c`, which holds the running compensation, in place
+    #[cfg(feature = "float")]
+    #[serde(rename = "fadd_kahan")]
+    FaddKahan,
     /// <https://capra.cs.cornell.edu/bril/lang/memory.html#operations>
     #[cfg(feature = "memory")]
     Alloc,
@@ -394,6 +435,24 @@ pub enum ValueOps {
     /// <https://capra.cs.cornell.edu/bril/lang/memory.html#operations>
     #[cfg(feature = "memory")]
     PtrAdd,
+    /// An extension which checks whether two pointers point into the same allocation, regardless of their offsets
+    #[cfg(feature = "memory")]
+    Samebase,
+    /// An extension which returns the number of cells between two pointers into the same allocation, like pointer subtraction in C
+    #[cfg(feature = "memory")]
+    Ptrdiff,
+    /// An extension which allocates memory like `alloc` that is freed automatically when the function that allocated it returns
+    #[cfg(feature = "memory")]
+    Alloca,
+    /// An extension which returns the number of allocations that have not been freed yet
+    #[cfg(feature = "memory")]
+    Heapblocks,
+    /// An extension which copies a number of cells starting at a pointer into a new allocation
+    #[cfg(feature = "memory")]
+    Dup,
+    /// An extension which copies every cell of an allocation, given by a pointer to its start, into a new allocation
+    #[cfg(feature = "memory")]
+    Memdup,
 }
 
 impl Display for ValueOps {
@@ -413,6 +472,18 @@ impl Display for ValueOps {
             ValueOps::Or => write!(f, "or"),
             ValueOps::Call => write!(f, "call"),
             ValueOps::Id => write!(f, "id"),
+            ValueOps::Dump => write!(f, "dump"),
+            ValueOps::Gcd => write!(f, "gcd"),
+            ValueOps::Lcm => write!(f, "lcm"),
+            ValueOps::Rotl => write!(f, "rotl"),
+            ValueOps::Rotr => write!(f, "rotr"),
+            ValueOps::Parity => write!(f, "parity"),
+            ValueOps::Undef => write!(f, "undef"),
+            ValueOps::Bool2int => write!(f, "bool2int"),
+            ValueOps::Int2bool => write!(f, "int2bool"),
+            ValueOps::Clock => write!(f, "clock"),
+            ValueOps::Argc => write!(f, "argc"),
+            ValueOps::UniqueId => write!(f, "unique_id"),
             #[cfg(feature = "ssa")]
             ValueOps::Phi => write!(f, "phi"),
             #[cfg(feature = "float")]
@@ -433,12 +504,30 @@ impl Display for ValueOps {
             ValueOps::Fle => write!(f, "fle"),
             #[cfg(feature = "float")]
             ValueOps::Fge => write!(f, "fge"),
+            #[cfg(feature = "float")]
+            ValueOps::Fapproxeq => write!(f, "fapproxeq"),
+            #[cfg(feature = "float")]
+            ValueOps::Fapprox => write!(f, "fapprox"),
+            #[cfg(feature = "float")]
+            ValueOps::FaddKahan => write!(f, "fadd_kahan"),
             #[cfg(feature = "memory")]
             ValueOps::Alloc => write!(f, "alloc"),
             #[cfg(feature = "memory")]
             ValueOps::Load => write!(f, "load"),
             #[cfg(feature = "memory")]
             ValueOps::PtrAdd => write!(f, "ptradd"),
+            #[cfg(feature = "memory")]
+            ValueOps::Samebase => write!(f, "samebase"),
+            #[cfg(feature = "memory")]
+            ValueOps::Ptrdiff => write!(f, "ptrdiff"),
+            #[cfg(feature = "memory")]
+            ValueOps::Alloca => write!(f, "alloca"),
+            #[cfg(feature = "memory")]
+            ValueOps::Heapblocks => write!(f, "heapblocks"),
+            #[cfg(feature = "memory")]
+            ValueOps::Dup => write!(f, "dup"),
+            #[cfg(feature = "memory")]
+            ValueOps::Memdup => write!(f, "memdup"),
         }
     }
 }
@@ -0,0 +1,12 @@
+#!/bin/bash
+
+# Compares --output-buffering line against end on a program that prints a lot, by the number of write syscalls and by time
+export file="../test/brilirs/output-buffering-end.bril"
+export arg="100000"
+
+for buffering in line end; do
+    echo "buffering is $buffering"
+    strace -f -c -e trace=write ./target/release/brilirs --output-buffering $buffering --text --file $file $arg > /dev/null
+done
+
+hyperfine --warmup 5 -L buffering line,end './target/release/brilirs --output-buffering {buffering} --text --file $file $arg > /dev/null'
@@ -0,0 +1,112 @@
+use bril_rs::{EffectOps, Instruction, Position, ValueOps};
+use fxhash::FxHashMap;
+use serde::Serialize;
+
+use crate::basic_block::{BBFunction, NumifiedInstruction};
+use crate::interp::{at_pos, Environment, JsonPos, Pointer};
+use crate::sites::Sites;
+
+// Tallies the allocations made by each `alloc`, `dup` and `memdup` instruction and how many of them were freed, so that a leak can be traced back to the instruction that made it
+#[derive(Debug, Default)]
+pub struct AllocSites<'a> {
+  sites: Sites<'a, Site<'a>>,
+  // The site that made each live allocation, by base
+  live: FxHashMap<usize, usize>,
+}
+
+#[derive(Debug)]
+struct Site<'a> {
+  func: &'a str,
+  op: ValueOps,
+  pos: Option<Position>,
+  allocated: u64,
+  freed: u64,
+}
+
+// A site as it is written in the JSON summary
+#[derive(Serialize)]
+pub struct JsonAllocSite<'a> {
+  op: String,
+  function: &'a str,
+  #[serde(flatten)]
+  pos: Option<JsonPos>,
+  allocated: u64,
+  freed: u64,
+}
+
+impl<'a> AllocSites<'a> {
+  // Called after the instruction at `block`, `instr` of `func` has been executed successfully
+  pub fn after(
+    &mut self,
+    env: &Environment,
+    func: &'a BBFunction,
+    block: usize,
+    instr: usize,
+    code: &Instruction,
+    numified: &NumifiedInstruction,
+  ) {
+    match code {
+      Instruction::Value {
+        op: op @ (ValueOps::Alloc | ValueOps::Dup | ValueOps::Memdup),
+        pos,
+        ..
+      } => {
+        let site = self.sites.id(&func.name, block, instr, || Site {
+          func: &func.name,
+          op: *op,
+          pos: *pos,
+          allocated: 0,
+          freed: 0,
+        });
+        self.sites[site].allocated += 1;
+        let p: &Pointer = env.get(&numified.dest.unwrap()).into();
+        self.live.insert(p.base, site);
+      }
+      Instruction::Effect {
+        op: EffectOps::Free,
+        ..
+      } => {
+        let p: &Pointer = env.get(&numified.args[0]).into();
+        // Allocations made before resuming from a checkpoint do not have a site
+        if let Some(site) = self.live.remove(&p.base) {
+          self.sites[site].freed += 1;
+        }
+      }
+      _ => {}
+    }
+  }
+
+  // A line for each site that was executed
+  pub fn report(&self) -> Vec<String> {
+    self
+      .sites
+      .iter()
+      .map(|s| {
+        format!(
+          "{} in @{}{}: {} allocated, {} freed, {} leaked",
+          s.op,
+          s.func,
+          at_pos(s.pos),
+          s.allocated,
+          s.freed,
+          s.allocated - s.freed
+        )
+      })
+      .collect()
+  }
+
+  // The same as `report` but for the JSON summary
+  pub fn json(&self) -> Vec<JsonAllocSite<'a>> {
+    self
+      .sites
+      .iter()
+      .map(|s| JsonAllocSite {
+        op: s.op.to_string(),
+        function: s.func,
+        pos: s.pos.map(JsonPos::from),
+        allocated: s.allocated,
+        freed: s.freed,
+      })
+      .collect()
+  }
+}
@@ -1,15 +1,22 @@
-use bril_rs::{Function, Instruction, Position, Program};
-use fxhash::FxHashMap;
+use std::collections::HashSet;
+
+use bril_rs::{EffectOps, Function, Instruction, Literal, Position, Program, Type, ValueOps};
+use fxhash::{FxHashMap, FxHashSet};
 
 use crate::error::{InterpError, PositionalInterpError};
+use crate::interp::IntLiteralPolicy;
+use crate::native::NativeFunctions;
 
 /// A program represented as basic blocks. This is the IR of brilirs
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BBProgram {
   #[doc(hidden)]
   pub index_of_main: Option<usize>,
   #[doc(hidden)]
   pub func_index: Vec<BBFunction>,
+  // The native functions that the program calls, which are numbered after the functions in `func_index`
+  #[doc(hidden)]
+  pub native_names: Vec<String>,
 }
 
 impl TryFrom<Program> for BBProgram {
@@ -23,24 +30,75 @@ impl TryFrom<Program> for BBProgram {
 impl BBProgram {
   /// Converts a [`Program`] into a [`BBProgram`]
   pub fn new(prog: Program) -> Result<Self, InterpError> {
+    Self::with_max_blocks(prog, None)
+  }
+
+  /// The same as [`BBProgram::new`] except that conversion stops with an error as soon as the program is found to have more than ```max_blocks``` basic blocks in total
+  ///
+  /// This bounds the memory used to hold a program that came from an untrusted source.
+  pub fn with_max_blocks(prog: Program, max_blocks: Option<usize>) -> Result<Self, InterpError> {
+    Self::with_int_literals(prog, max_blocks, IntLiteralPolicy::default())
+  }
+
+  /// The same as [`BBProgram::with_max_blocks`] except that an `int` constant whose literal is outside of the range of an `i64` is handled according to ```int_literals```
+  ///
+  /// Such a literal has already been parsed as the nearest float, by `serde` from JSON or by `bril2json` from text, so wrapping it is only exact when the literal is also a float, such as a power of two. Text parsed with `bril2json::parse_abstract_program_from_read_wrapping` has already had the digits themselves wrapped.
+  pub fn with_int_literals(
+    prog: Program,
+    max_blocks: Option<usize>,
+    int_literals: IntLiteralPolicy,
+  ) -> Result<Self, InterpError> {
+    Self::build(prog, max_blocks, int_literals, &[])
+  }
+
+  /// The same as [`BBProgram::new`] except that the program may call the functions of ```natives``` that it does not define itself
+  pub fn with_natives(prog: Program, natives: &NativeFunctions) -> Result<Self, InterpError> {
+    let names: Vec<&str> = natives.names().collect();
+    Self::build(prog, None, IntLiteralPolicy::default(), &names)
+  }
+
+  fn build(
+    prog: Program,
+    max_blocks: Option<usize>,
+    int_literals: IntLiteralPolicy,
+    natives: &[&str],
+  ) -> Result<Self, InterpError> {
     let num_funcs = prog.functions.len();
 
-    let func_map: FxHashMap<String, usize> = prog
+    let mut func_map: FxHashMap<String, usize> = prog
       .functions
       .iter()
       .enumerate()
       .map(|(idx, func)| (func.name.clone(), idx))
       .collect();
+    // A function defined in the program takes the place of a native function of the same name
+    let native_names: Vec<String> = natives
+      .iter()
+      .filter(|name| !func_map.contains_key(**name))
+      .map(ToString::to_string)
+      .collect();
+    native_names.iter().enumerate().for_each(|(i, name)| {
+      func_map.insert(name.clone(), num_funcs + i);
+    });
 
+    let mut num_blocks = 0;
     let func_index = prog
       .functions
       .into_iter()
-      .map(|func| BBFunction::new(func, &func_map))
+      .map(|func| {
+        let func = BBFunction::new(func, &func_map, int_literals)?;
+        num_blocks += func.blocks.len();
+        match max_blocks {
+          Some(max) if num_blocks > max => Err(InterpError::ProgramTooLarge(max)),
+          _ => Ok(func),
+        }
+      })
       .collect::<Result<Vec<BBFunction>, InterpError>>()?;
 
     let bb = Self {
       index_of_main: func_map.get(&"main".to_string()).cloned(),
       func_index,
+      native_names,
     };
     if bb.func_index.len() != num_funcs {
       Err(InterpError::DuplicateFunction)
@@ -53,10 +111,106 @@ impl BBProgram {
   pub fn get(&self, func_name: usize) -> Option<&BBFunction> {
     self.func_index.get(func_name)
   }
+
+  /// Every distinct opcode that appears in the program, as it is written in Bril text, i.e. `const`, `add` or `print`
+  ///
+  /// This is every opcode in the program whether or not it would be executed, so a host that can run a subset of Bril can check ahead of time that it supports the program and list what it is missing.
+  ///
+  /// ```
+  /// use brilirs::basic_block::BBProgram;
+  /// use bril_rs::Program;
+  ///
+  /// let src = "@main {\n  a: int = const 1;\n  b: int = add a a;\n  c: int = add b a;\n  print c;\n  jmp .end;\n.end:\n  d: int = call @f;\n}\n@f: int {\n  t: bool = const true;\n  x: int = const 2;\n  br t .a .b;\n.a:\n  ret x;\n.b:\n  y: int = mul x x;\n  ret y;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  ///
+  /// let mut ops: Vec<_> = bbprog.opcodes_used().into_iter().collect();
+  /// ops.sort();
+  /// assert_eq!(ops, ["add", "br", "call", "const", "jmp", "mul", "print", "ret"]);
+  /// ```
+  #[must_use]
+  pub fn opcodes_used(&self) -> HashSet<String> {
+    self
+      .func_index
+      .iter()
+      .flat_map(|f| &f.blocks)
+      .flat_map(|b| &b.instrs)
+      .map(opcode)
+      .collect()
+  }
+
+  // The indices of the functions whose calls only depend on their arguments and do nothing but return a value, so that a call with the same arguments always does the same thing. These do not touch memory, print, read the clock or the arguments of the program, speculate, or call a function that is not pure
+  pub(crate) fn pure_functions(&self) -> FxHashSet<usize> {
+    let is_pure_op = |instr: &Instruction| match instr {
+      Instruction::Constant { .. } => true,
+      Instruction::Value { op, .. } => !matches!(
+        op,
+        ValueOps::Dump
+          | ValueOps::Clock
+          | ValueOps::Argc
+          | ValueOps::UniqueId
+          | ValueOps::Alloc
+          | ValueOps::Alloca
+          | ValueOps::Load
+          | ValueOps::Heapblocks
+          | ValueOps::Dup
+          | ValueOps::Memdup
+      ),
+      Instruction::Effect { op, .. } => matches!(
+        op,
+        EffectOps::Jump
+          | EffectOps::Branch
+          | EffectOps::Call
+          | EffectOps::Return
+          | EffectOps::Nop
+          | EffectOps::Fence
+      ),
+    };
+    let mut pure: FxHashSet<usize> = (0..self.func_index.len())
+      .filter(|i| {
+        self.func_index[*i]
+          .blocks
+          .iter()
+          .flat_map(|b| &b.instrs)
+          .all(is_pure_op)
+      })
+      .collect();
+    // Calling a function that is not pure makes the caller not pure, which can make its own callers not pure
+    loop {
+      let impure: Vec<usize> = pure
+        .iter()
+        .copied()
+        .filter(|i| {
+          self.func_index[*i]
+            .blocks
+            .iter()
+            .flat_map(|b| &b.numified_instrs)
+            .any(|n| n.funcs.iter().any(|f| !pure.contains(f)))
+        })
+        .collect();
+      if impure.is_empty() {
+        return pure;
+      }
+      impure.iter().for_each(|i| {
+        pure.remove(i);
+      });
+    }
+  }
+}
+
+// The opcode of `instr` as it is written in Bril text
+pub(crate) fn opcode(instr: &Instruction) -> String {
+  match instr {
+    Instruction::Constant { op, .. } => op.to_string(),
+    Instruction::Value { op, .. } => op.to_string(),
+    Instruction::Effect { op, .. } => op.to_string(),
+  }
 }
 
 #[doc(hidden)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BasicBlock {
   pub label: Option<String>,
   // These two vecs work in parallel
@@ -79,13 +233,42 @@ impl BasicBlock {
 }
 
 #[doc(hidden)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct NumifiedInstruction {
   pub dest: Option<usize>,
   pub args: Vec<usize>,
   pub funcs: Vec<usize>,
 }
 
+// An `int` constant whose literal did not fit in an `i64` has been parsed as a float instead
+fn check_int_literal(
+  instr: &mut Instruction,
+  int_literals: IntLiteralPolicy,
+) -> Result<(), PositionalInterpError> {
+  // 2^63 and 2^64
+  const MAX: f64 = 9_223_372_036_854_775_808.0;
+  const WRAP: f64 = 18_446_744_073_709_551_616.0;
+  if let Instruction::Constant {
+    const_type: Type::Int,
+    value,
+    pos,
+    ..
+  } = instr
+  {
+    if let Literal::Float(f) = *value {
+      if f.is_finite() && f.fract() == 0.0 && !(-MAX..MAX).contains(&f) {
+        match int_literals {
+          IntLiteralPolicy::Error => {
+            return Err(InterpError::IntegerLiteralOutOfRange(format!("{f:.0}")).add_pos(*pos))
+          }
+          IntLiteralPolicy::Wrap => *value = Literal::Int(f.rem_euclid(WRAP) as u64 as i64),
+        }
+      }
+    }
+  }
+  Ok(())
+}
+
 fn get_num_from_map(
   variable_name: &str,
   // The total number of variables so far. Only grows
@@ -165,7 +348,7 @@ impl NumifiedInstruction {
 }
 
 #[doc(hidden)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BBFunction {
   pub name: String,
   pub args: Vec<bril_rs::Argument>,
@@ -176,19 +359,113 @@ pub struct BBFunction {
   // These replacements are found for function args and for code in the BasicBlocks
   pub num_of_vars: usize,
   pub args_as_nums: Vec<usize>,
+  // The name of each numbered variable, so that values can be shown to the user
+  pub var_names: Vec<String>,
   pub pos: Option<Position>,
 }
 
 impl BBFunction {
-  fn new(f: Function, func_map: &FxHashMap<String, usize>) -> Result<Self, InterpError> {
-    let (mut func, label_map) = Self::find_basic_blocks(f, func_map)?;
+  fn new(
+    f: Function,
+    func_map: &FxHashMap<String, usize>,
+    int_literals: IntLiteralPolicy,
+  ) -> Result<Self, InterpError> {
+    let (mut func, label_map) = Self::find_basic_blocks(f, func_map, int_literals)?;
     func.build_cfg(label_map)?;
     Ok(func)
   }
 
+  /// The name of the function, without the `@`
+  #[must_use]
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// The arguments of the function with their names and types, in the order that they are passed
+  ///
+  /// ```
+  /// use brilirs::basic_block::BBProgram;
+  /// use bril_rs::{Program, Type};
+  ///
+  /// let src = "@scale(v: ptr<float>, n: int, flip: bool): float {\n  zero: float = const 0;\n  ret zero;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  /// let func = bbprog.get(0).unwrap();
+  ///
+  /// assert_eq!(func.name(), "scale");
+  /// assert_eq!(func.arity(), 3);
+  /// let args: Vec<(&str, &Type)> = func
+  ///   .args()
+  ///   .iter()
+  ///   .map(|a| (a.name.as_str(), &a.arg_type))
+  ///   .collect();
+  /// assert_eq!(
+  ///   args,
+  ///   [
+  ///     ("v", &Type::Pointer(Box::new(Type::Float))),
+  ///     ("n", &Type::Int),
+  ///     ("flip", &Type::Bool)
+  ///   ]
+  /// );
+  /// assert_eq!(func.return_type(), Some(&Type::Float));
+  /// ```
+  #[must_use]
+  pub fn args(&self) -> &[bril_rs::Argument] {
+    &self.args
+  }
+
+  /// The number of arguments that the function takes
+  #[must_use]
+  pub const fn arity(&self) -> usize {
+    self.args.len()
+  }
+
+  /// The type of the value that the function returns, or `None` if it does not return one
+  #[must_use]
+  pub const fn return_type(&self) -> Option<&Type> {
+    self.return_type.as_ref()
+  }
+
+  /// The cyclomatic complexity of the function's control flow graph, `E - N + 2`
+  ///
+  /// Blocks that leave the function are given an edge to a single exit node so that each early return counts as its own path.
+  ///
+  /// ```
+  /// use brilirs::basic_block::BBProgram;
+  /// use bril_rs::Program;
+  ///
+  /// let src = "@main(n: int) {
+  ///   zero: int = const 0;
+  ///   neg: bool = lt n zero;
+  ///   br neg .ret .loop;
+  /// .ret:
+  ///   ret;
+  /// .loop:
+  ///   one: int = const 1;
+  ///   n: int = sub n one;
+  ///   done: bool = eq n zero;
+  ///   br done .end .loop;
+  /// .end:
+  /// }";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  /// assert_eq!(bbprog.get(0).unwrap().cyclomatic_complexity(), 3);
+  /// ```
+  #[must_use]
+  pub fn cyclomatic_complexity(&self) -> usize {
+    let edges: usize = self.blocks.iter().map(|b| b.exit.len().max(1)).sum();
+    // With the extra exit node, E - (N + 1) + 2
+    edges + 1 - self.blocks.len()
+  }
+
   fn find_basic_blocks(
     func: bril_rs::Function,
     func_map: &FxHashMap<String, usize>,
+    int_literals: IntLiteralPolicy,
   ) -> Result<(Self, FxHashMap<String, usize>), PositionalInterpError> {
     let mut blocks = Vec::new();
     let mut label_map = FxHashMap::default();
@@ -223,7 +500,8 @@ impl BBFunction {
           pos,
         }) if op == bril_rs::EffectOps::Jump
           || op == bril_rs::EffectOps::Branch
-          || op == bril_rs::EffectOps::Return =>
+          || op == bril_rs::EffectOps::Return
+          || op == bril_rs::EffectOps::Guard =>
         {
           let i = bril_rs::Instruction::Effect {
             op,
@@ -245,7 +523,8 @@ impl BBFunction {
           blocks.push(curr_block);
           curr_block = BasicBlock::new();
         }
-        bril_rs::Code::Instruction(code) => {
+        bril_rs::Code::Instruction(mut code) => {
+          check_int_literal(&mut code, int_literals)?;
           curr_block.numified_instrs.push(NumifiedInstruction::new(
             &code,
             &mut num_of_vars,
@@ -264,6 +543,11 @@ impl BBFunction {
       blocks.push(curr_block);
     }
 
+    let mut var_names = vec![String::new(); num_of_vars];
+    num_var_map
+      .into_iter()
+      .for_each(|(name, i)| var_names[i] = name);
+
     Ok((
       Self {
         name: func.name,
@@ -272,6 +556,7 @@ impl BBFunction {
         blocks,
         args_as_nums,
         num_of_vars,
+        var_names,
         pos: func.pos,
       },
       label_map,
@@ -281,6 +566,26 @@ impl BBFunction {
   fn build_cfg(&mut self, label_map: FxHashMap<String, usize>) -> Result<(), InterpError> {
     let last_idx = self.blocks.len() - 1;
     for (i, block) in self.blocks.iter_mut().enumerate() {
+      // A guard jumps to its label when speculation is aborted and otherwise falls through, if there is a block to fall through to
+      if let Some(bril_rs::Instruction::Effect {
+        op: bril_rs::EffectOps::Guard,
+        labels,
+        ..
+      }) = block.instrs.last()
+      {
+        let l = labels
+          .first()
+          .ok_or(InterpError::BadNumLabels(1, labels.len()))?;
+        block.exit.push(
+          *label_map
+            .get(l)
+            .ok_or_else(|| InterpError::MissingLabel(l.to_string()))?,
+        );
+        if i < last_idx {
+          block.exit.push(i + 1);
+        }
+        continue;
+      }
       // If we're before the last block
       if i < last_idx {
         // Get the last instruction
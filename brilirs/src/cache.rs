@@ -0,0 +1,106 @@
+use bril_rs::{EffectOps, Instruction, ValueOps};
+use fxhash::FxHashMap;
+
+use crate::basic_block::BBProgram;
+use crate::error::{InterpError, PositionalInterpError};
+use crate::interp::{run_main, Hooks, InterpOptions};
+
+/// Runs a program and remembers what it printed for each list of arguments so that running it again with the same arguments replays the output instead of executing anything
+///
+/// This is for hosts that run the same program over and over, such as a server. It relies on a run being a function of the arguments of "main", which holds for any program that only communicates through `print`. [`RunCache::new`] rejects programs that use `dump`, which writes to `profiling_out` as it runs, and `debugbreak`, which hands control to the host. Only runs that succeed are cached, and nothing is written to `profiling_out` when a run is replayed.
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, cache::RunCache, interp::InterpOptions};
+/// use bril_rs::Program;
+///
+/// let src = "@main(n: int) {\n  one: int = const 1;\n  m: int = add n one;\n  print m;\n}";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+/// let options = InterpOptions::default();
+/// let mut cache = RunCache::new(&bbprog, &options).unwrap();
+///
+/// let run = |cache: &mut RunCache, n: &str| {
+///   let mut out = Vec::new();
+///   let executed = cache.run(&mut out, &[n.to_string()], std::io::sink()).unwrap();
+///   (out, executed)
+/// };
+/// assert_eq!(run(&mut cache, "41"), (b"42\n".to_vec(), 3));
+/// // The same arguments are replayed without executing any instructions
+/// assert_eq!(run(&mut cache, "41"), (b"42\n".to_vec(), 0));
+/// assert_eq!(run(&mut cache, "1"), (b"2\n".to_vec(), 3));
+/// ```
+#[derive(Debug)]
+pub struct RunCache<'a> {
+  prog: &'a BBProgram,
+  options: &'a InterpOptions,
+  outputs: FxHashMap<Vec<String>, Vec<u8>>,
+}
+
+impl<'a> RunCache<'a> {
+  /// Creates an empty cache for running ```prog``` with ```options```, or reports the first instruction that could make a run more than a function of its arguments
+  pub fn new(
+    prog: &'a BBProgram,
+    options: &'a InterpOptions,
+  ) -> Result<Self, PositionalInterpError> {
+    for instr in prog
+      .func_index
+      .iter()
+      .flat_map(|f| &f.blocks)
+      .flat_map(|b| &b.instrs)
+    {
+      let op = match instr {
+        Instruction::Value {
+          op: ValueOps::Dump, ..
+        } => "dump",
+        Instruction::Effect {
+          op: EffectOps::Debugbreak,
+          ..
+        } => "debugbreak",
+        _ => continue,
+      };
+      return Err(InterpError::UncacheableInstruction(op.to_string()).add_pos(instr.get_pos()));
+    }
+    Ok(Self {
+      prog,
+      options,
+      outputs: FxHashMap::default(),
+    })
+  }
+
+  /// Runs "main" with ```input_args``` and writes what it prints to ```out```, or writes what it printed the last time that it was run with the same arguments. Returns the number of instructions that were executed, which is 0 when the output was replayed
+  pub fn run<T: std::io::Write, U: std::io::Write>(
+    &mut self,
+    mut out: T,
+    input_args: &[String],
+    profiling_out: U,
+  ) -> Result<u32, PositionalInterpError> {
+    let io_error = |e| PositionalInterpError::new(InterpError::IoError(Box::new(e)));
+    if let Some(output) = self.outputs.get(input_args) {
+      out
+        .write_all(output)
+        .and_then(|_| out.flush())
+        .map_err(io_error)?;
+      return Ok(0);
+    }
+    let mut output = Vec::new();
+    let result = run_main(
+      self.prog,
+      &mut output,
+      input_args,
+      profiling_out,
+      self.options,
+      None,
+      Hooks::default(),
+    );
+    // What was printed before an error is still passed on
+    out
+      .write_all(&output)
+      .and_then(|_| out.flush())
+      .map_err(io_error)?;
+    let (_, instruction_count) = result?;
+    self.outputs.insert(input_args.to_vec(), output);
+    Ok(instruction_count)
+  }
+}
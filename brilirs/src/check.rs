@@ -44,6 +44,20 @@ fn check_asmt_type(expected: &bril_rs::Type, actual: &bril_rs::Type) -> Result<(
   }
 }
 
+// Checks that an argument of type `actual` can be passed to a function argument of type `expected`. Under relaxed calls an int may be promoted to a float, but nothing else is allowed
+#[inline(always)]
+fn check_call_arg_type(
+  actual: &bril_rs::Type,
+  expected: &bril_rs::Type,
+  relaxed_calls: bool,
+) -> Result<(), InterpError> {
+  if relaxed_calls && actual == &Type::Int && expected == &Type::Float {
+    Ok(())
+  } else {
+    check_asmt_type(actual, expected)
+  }
+}
+
 #[inline(always)]
 fn update_env<'a>(
   env: &mut FxHashMap<&'a str, &'a Type>,
@@ -88,6 +102,7 @@ fn type_check_instruction<'a>(
   func: &BBFunction,
   prog: &BBProgram,
   env: &mut FxHashMap<&'a str, &'a Type>,
+  relaxed_calls: bool,
 ) -> Result<(), InterpError> {
   match instr {
     Instruction::Constant {
@@ -103,7 +118,15 @@ fn type_check_instruction<'a>(
       update_env(env, dest, const_type)
     }
     Instruction::Value {
-      op: ValueOps::Add | ValueOps::Sub | ValueOps::Mul | ValueOps::Div,
+      op:
+        ValueOps::Add
+        | ValueOps::Sub
+        | ValueOps::Mul
+        | ValueOps::Div
+        | ValueOps::Gcd
+        | ValueOps::Lcm
+        | ValueOps::Rotl
+        | ValueOps::Rotr,
       dest,
       op_type,
       args,
@@ -184,6 +207,98 @@ fn type_check_instruction<'a>(
       check_asmt_type(op_type, get_type(env, 0, args)?)?;
       update_env(env, dest, op_type)
     }
+    Instruction::Value {
+      op: ValueOps::Bool2int,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(1, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Bool, get_type(env, 0, args)?)?;
+      check_asmt_type(&Type::Int, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Parity,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(1, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Int, get_type(env, 0, args)?)?;
+      check_asmt_type(&Type::Int, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Int2bool,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(1, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Int, get_type(env, 0, args)?)?;
+      check_asmt_type(&Type::Bool, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Clock | ValueOps::Argc | ValueOps::Heapblocks | ValueOps::UniqueId,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(0, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Int, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Undef,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(0, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Dump,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(1, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(1, labels)?;
+      check_asmt_type(op_type, get_type(env, 0, args)?)?;
+      update_env(env, dest, op_type)
+    }
     Instruction::Value {
       op: ValueOps::Fadd | ValueOps::Fsub | ValueOps::Fmul | ValueOps::Fdiv,
       dest,
@@ -218,6 +333,42 @@ fn type_check_instruction<'a>(
       check_asmt_type(&Type::Bool, op_type)?;
       update_env(env, dest, op_type)
     }
+    Instruction::Value {
+      op: ValueOps::Fapproxeq | ValueOps::Fapprox,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(3, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Float, get_type(env, 0, args)?)?;
+      check_asmt_type(&Type::Float, get_type(env, 1, args)?)?;
+      check_asmt_type(&Type::Float, get_type(env, 2, args)?)?;
+      check_asmt_type(&Type::Bool, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::FaddKahan,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(3, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      check_asmt_type(&Type::Float, get_type(env, 0, args)?)?;
+      check_asmt_type(&Type::Float, get_type(env, 1, args)?)?;
+      check_asmt_type(&Type::Float, get_type(env, 2, args)?)?;
+      check_asmt_type(&Type::Float, op_type)?;
+      update_env(env, dest, op_type)
+    }
     Instruction::Value {
       op: ValueOps::Call,
       dest,
@@ -229,7 +380,10 @@ fn type_check_instruction<'a>(
     } => {
       check_num_funcs(1, funcs)?;
       check_num_labels(0, labels)?;
-      let callee_func = prog.func_index.get(num_instr.funcs[0]).unwrap();
+      // Native functions do not declare their types
+      let Some(callee_func) = prog.func_index.get(num_instr.funcs[0]) else {
+        return update_env(env, dest, op_type);
+      };
 
       if args.len() != callee_func.args.len() {
         return Err(InterpError::BadNumArgs(callee_func.args.len(), args.len()));
@@ -242,11 +396,14 @@ fn type_check_instruction<'a>(
             .get(arg_name as &str)
             .ok_or_else(|| InterpError::VarUndefined(arg_name.to_string()))?;
 
-          check_asmt_type(ty, &expected_arg.arg_type)
+          check_call_arg_type(ty, &expected_arg.arg_type, relaxed_calls)
         })?;
 
       match &callee_func.return_type {
-        None => Err(InterpError::NonEmptyRetForFunc(callee_func.name.clone())),
+        None => Err(InterpError::NonEmptyRetForFunc(
+          callee_func.name.clone(),
+          None,
+        )),
         Some(t) => check_asmt_type(op_type, t),
       }?;
       update_env(env, dest, op_type)
@@ -271,7 +428,7 @@ fn type_check_instruction<'a>(
       update_env(env, dest, op_type)
     }
     Instruction::Value {
-      op: ValueOps::Alloc,
+      op: ValueOps::Alloc | ValueOps::Alloca,
       dest,
       op_type,
       args,
@@ -303,7 +460,24 @@ fn type_check_instruction<'a>(
       update_env(env, dest, op_type)
     }
     Instruction::Value {
-      op: ValueOps::PtrAdd,
+      op: ValueOps::Memdup,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(1, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      let ty0 = get_type(env, 0, args)?;
+      get_ptr_type(ty0)?;
+      check_asmt_type(ty0, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::PtrAdd | ValueOps::Dup,
       dest,
       op_type,
       args,
@@ -320,6 +494,41 @@ fn type_check_instruction<'a>(
       check_asmt_type(ty0, op_type)?;
       update_env(env, dest, op_type)
     }
+    Instruction::Value {
+      op: ValueOps::Samebase,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(2, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      get_ptr_type(get_type(env, 0, args)?)?;
+      get_ptr_type(get_type(env, 1, args)?)?;
+      check_asmt_type(&Type::Bool, op_type)?;
+      update_env(env, dest, op_type)
+    }
+    Instruction::Value {
+      op: ValueOps::Ptrdiff,
+      dest,
+      op_type,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(2, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      let ty0 = get_type(env, 0, args)?;
+      get_ptr_type(ty0)?;
+      check_asmt_type(ty0, get_type(env, 1, args)?)?;
+      check_asmt_type(&Type::Int, op_type)?;
+      update_env(env, dest, op_type)
+    }
     Instruction::Effect {
       op: EffectOps::Jump,
       args,
@@ -364,7 +573,10 @@ fn type_check_instruction<'a>(
           if args.is_empty() {
             Ok(())
           } else {
-            Err(InterpError::NonEmptyRetForFunc(func.name.clone()))
+            Err(InterpError::NonEmptyRetForFunc(
+              func.name.clone(),
+              Some((*get_type(env, 0, args)?).clone()),
+            ))
           }
         }
       }
@@ -384,7 +596,7 @@ fn type_check_instruction<'a>(
       })
     }
     Instruction::Effect {
-      op: EffectOps::Nop,
+      op: EffectOps::Nop | EffectOps::Debugbreak | EffectOps::Fence,
       args,
       funcs,
       labels,
@@ -404,7 +616,10 @@ fn type_check_instruction<'a>(
     } => {
       check_num_funcs(1, funcs)?;
       check_num_labels(0, labels)?;
-      let callee_func = prog.func_index.get(num_instr.funcs[0]).unwrap();
+      // Native functions do not declare their types
+      let Some(callee_func) = prog.func_index.get(num_instr.funcs[0]) else {
+        return Ok(());
+      };
 
       if args.len() != callee_func.args.len() {
         return Err(InterpError::BadNumArgs(callee_func.args.len(), args.len()));
@@ -417,14 +632,15 @@ fn type_check_instruction<'a>(
             .get(arg_name as &str)
             .ok_or_else(|| InterpError::VarUndefined(arg_name.to_string()))?;
 
-          check_asmt_type(ty, &expected_arg.arg_type)
+          check_call_arg_type(ty, &expected_arg.arg_type, relaxed_calls)
         })?;
 
-      if callee_func.return_type.is_some() {
-        Err(InterpError::NonEmptyRetForFunc(callee_func.name.clone()))
-      } else {
-        Ok(())
-      }
+      callee_func.return_type.as_ref().map_or(Ok(()), |t| {
+        Err(InterpError::NonEmptyRetForFunc(
+          callee_func.name.clone(),
+          Some(t.clone()),
+        ))
+      })
     }
     Instruction::Effect {
       op: EffectOps::Store,
@@ -454,18 +670,38 @@ fn type_check_instruction<'a>(
       Ok(())
     }
     Instruction::Effect {
-      op: EffectOps::Speculate | EffectOps::Guard | EffectOps::Commit,
-      args: _,
-      funcs: _,
-      labels: _,
+      op: EffectOps::Speculate | EffectOps::Commit,
+      args,
+      funcs,
+      labels,
+      pos: _,
+    } => {
+      check_num_args(0, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(0, labels)?;
+      Ok(())
+    }
+    Instruction::Effect {
+      op: EffectOps::Guard,
+      args,
+      funcs,
+      labels,
       pos: _,
     } => {
-      unimplemented!()
+      check_num_args(1, args)?;
+      check_num_funcs(0, funcs)?;
+      check_num_labels(1, labels)?;
+      check_asmt_type(&Type::Bool, get_type(env, 0, args)?)?;
+      Ok(())
     }
   }
 }
 
-fn type_check_func(bbfunc: &BBFunction, bbprog: &BBProgram) -> Result<(), PositionalInterpError> {
+fn type_check_func(
+  bbfunc: &BBFunction,
+  bbprog: &BBProgram,
+  relaxed_calls: bool,
+) -> Result<(), PositionalInterpError> {
   let mut env: FxHashMap<&str, &Type> =
     FxHashMap::with_capacity_and_hasher(20, fxhash::FxBuildHasher::default());
   bbfunc.args.iter().for_each(|a| {
@@ -482,7 +718,7 @@ fn type_check_func(bbfunc: &BBFunction, bbprog: &BBProgram) -> Result<(), Positi
       .iter()
       .zip(block.numified_instrs.iter())
       .try_for_each(|(i, num_i)| {
-        type_check_instruction(i, num_i, bbfunc, bbprog, &mut env)
+        type_check_instruction(i, num_i, bbfunc, bbprog, &mut env, relaxed_calls)
           .map_err(|e| e.add_pos(i.get_pos()))
       })?;
     done_list.push(b);
@@ -496,6 +732,34 @@ fn type_check_func(bbfunc: &BBFunction, bbprog: &BBProgram) -> Result<(), Positi
   Ok(())
 }
 
+fn check_ssa_func(bbfunc: &BBFunction) -> Result<(), PositionalInterpError> {
+  // Function arguments are defined on entry so they count as the first definition
+  let mut defined = vec![false; bbfunc.num_of_vars];
+  bbfunc.args_as_nums.iter().for_each(|a| defined[*a] = true);
+
+  bbfunc
+    .blocks
+    .iter()
+    .flat_map(|b| b.instrs.iter().zip(b.numified_instrs.iter()))
+    .try_for_each(|(i, num_i)| match (i, num_i.dest) {
+      (Instruction::Constant { dest, .. } | Instruction::Value { dest, .. }, Some(d)) => {
+        if std::mem::replace(&mut defined[d], true) {
+          Err(InterpError::MultipleDefinitionsInSSA(dest.clone()).add_pos(i.get_pos()))
+        } else {
+          Ok(())
+        }
+      }
+      _ => Ok(()),
+    })
+}
+
+/// Validates that a Bril program is in strict SSA form, i.e. that each
+/// variable is statically assigned at most once in its function. Phi
+/// nodes count as assignments.
+pub fn check_ssa(bbprog: &BBProgram) -> Result<(), PositionalInterpError> {
+  bbprog.func_index.iter().try_for_each(check_ssa_func)
+}
+
 /// Provides validation of Bril programs. This involves
 /// statically checking the types and number of arguments to Bril
 /// instructions.
@@ -503,5 +767,103 @@ pub fn type_check(bbprog: &BBProgram) -> Result<(), PositionalInterpError> {
   bbprog
     .func_index
     .iter()
-    .try_for_each(|bbfunc| type_check_func(bbfunc, bbprog))
+    .try_for_each(|bbfunc| type_check_func(bbfunc, bbprog, false))
+}
+
+/// The same as [`type_check`] except that an `int` may be passed to a function argument of type `float`
+///
+/// This matches ```interp::InterpOptions::relaxed_calls``` where the `int` is converted to a `float` when the call is made.
+pub fn type_check_relaxed_calls(bbprog: &BBProgram) -> Result<(), PositionalInterpError> {
+  bbprog
+    .func_index
+    .iter()
+    .try_for_each(|bbfunc| type_check_func(bbfunc, bbprog, true))
+}
+
+// Checks every `ret` of a function against its return type. The type of a variable is taken from where it is assigned, as a program that passes `type_check` gives each variable a single type
+fn check_return_types_func(bbfunc: &BBFunction) -> Result<(), PositionalInterpError> {
+  let mut types: FxHashMap<&str, &Type> = bbfunc
+    .args
+    .iter()
+    .map(|a| (a.name.as_str(), &a.arg_type))
+    .collect();
+  let instrs = bbfunc.blocks.iter().flat_map(|b| &b.instrs);
+  instrs.clone().for_each(|i| match i {
+    Instruction::Constant {
+      dest, const_type, ..
+    } => {
+      types.entry(dest).or_insert(const_type);
+    }
+    Instruction::Value { dest, op_type, .. } => {
+      types.entry(dest).or_insert(op_type);
+    }
+    Instruction::Effect { .. } => {}
+  });
+
+  instrs
+    .filter_map(|i| match i {
+      Instruction::Effect {
+        op: EffectOps::Return,
+        args,
+        ..
+      } => Some((i, args)),
+      _ => None,
+    })
+    .try_for_each(|(i, args)| {
+      let returned = args
+        .first()
+        .map(|a| {
+          types
+            .get(a.as_str())
+            .copied()
+            .ok_or_else(|| InterpError::VarUndefined(a.clone()))
+        })
+        .transpose()
+        .map_err(|e| e.add_pos(i.get_pos()))?;
+      if bbfunc.return_type.as_ref() == returned {
+        Ok(())
+      } else {
+        Err(
+          InterpError::ReturnTypeMismatch(
+            bbfunc.name.clone(),
+            bbfunc.return_type.clone(),
+            returned.cloned(),
+          )
+          .add_pos(i.get_pos()),
+        )
+      }
+    })
+}
+
+/// Validates that every `ret` in each function of a Bril program agrees with the return type of the function: it has a value exactly when the function has a return type, and the value is of that type
+///
+/// This is also checked by [`type_check`], along with everything else. On its own it reports the first `ret` that does not agree as the more specific `ReturnTypeMismatch` rather than a general type error, which is clearer when looking for a code generator that gets returns wrong.
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, check::check_return_types};
+/// use bril_rs::Program;
+///
+/// let check = |src: &str| {
+///   let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+///     .try_into()
+///     .unwrap();
+///   let bbprog: BBProgram = prog.try_into().unwrap();
+///   check_return_types(&bbprog).map_err(|e| e.to_string())
+/// };
+///
+/// assert!(check("@main { x: int = call @f; }\n@f: int {\n  r: int = const 1;\n  ret r;\n}").is_ok());
+/// assert_eq!(
+///   check("@main { x: int = call @f; }\n@f: int {\n  r: bool = const true;\n  ret r;\n}").unwrap_err(),
+///   "Line 4, Column 3: `@f` is declared to return `int` but returns `bool`"
+/// );
+/// assert_eq!(
+///   check("@main { x: int = call @f; }\n@f: int {\n  ret;\n}").unwrap_err(),
+///   "Line 3, Column 3: `@f` is declared to return `int` but returns nothing"
+/// );
+/// ```
+pub fn check_return_types(bbprog: &BBProgram) -> Result<(), PositionalInterpError> {
+  bbprog
+    .func_index
+    .iter()
+    .try_for_each(check_return_types_func)
 }
@@ -0,0 +1,339 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::error::InterpError;
+use crate::interp::{sorted_entries, Environment, Heap, Pointer, Value};
+
+// The first line of every checkpoint. Bump the version whenever the format below changes so that old checkpoints are rejected instead of misread.
+const CHECKPOINT_HEADER: &str = "brilirs-checkpoint v3";
+
+// A suspended call to a function. `block` and `instr` locate the instruction to resume from in the innermost frame and the call instruction that is waiting on its callee in every other frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Frame {
+  pub(crate) func: String,
+  pub(crate) block: usize,
+  pub(crate) instr: usize,
+  // The label of the previously executed block which phi nodes will read from
+  pub(crate) last_label: Option<String>,
+  // Where the result of the pending call should be stored
+  pub(crate) dest: Option<usize>,
+}
+
+/// The full state of a suspended execution
+///
+/// This is the call stack of "main" down to the innermost frame, the environment, the heap, and the dynamic instruction count. It is taken by ```interp::execute_main_until``` and resumed by ```interp::resume_main_until```.
+///
+/// Checkpoints are written in a versioned, line based text format. Floats are stored by their bits and heap locations by their base and offset, so resuming produces exactly the same output as an uninterrupted run.
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, checkpoint::Checkpoint, interp};
+/// use bril_rs::Program;
+///
+/// let src = "@main { i: int = const 0; one: int = const 1; ten: int = const 10;
+///   .loop: print i; i: int = add i one; done: bool = eq i ten; br done .end .loop; .end: }";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+/// let options = interp::InterpOptions::default();
+///
+/// let mut full = Vec::new();
+/// interp::execute_main_with(&bbprog, &mut full, &[], std::io::sink(), &options).unwrap();
+///
+/// let mut resumed = Vec::new();
+/// let checkpoint =
+///   interp::execute_main_until(&bbprog, &mut resumed, &[], std::io::sink(), &options, Some(20))
+///     .unwrap()
+///     .unwrap();
+/// let bytes = checkpoint.to_bytes();
+/// let checkpoint = Checkpoint::from_bytes(&bytes).unwrap();
+/// interp::resume_main_until(&bbprog, &mut resumed, std::io::sink(), &options, checkpoint, None)
+///   .unwrap();
+///
+/// assert_eq!(full, resumed);
+/// ```
+#[derive(Debug)]
+pub struct Checkpoint {
+  pub(crate) instruction_count: u32,
+  // So that `unique_id` does not repeat a value from before the checkpoint
+  pub(crate) unique_ids: i64,
+  // Ordered from "main" to the innermost call
+  pub(crate) frames: Vec<Frame>,
+  pub(crate) env: Environment,
+  pub(crate) heap: Heap,
+  // The allocations made by `alloca` in the suspended calls, as the depth of the call and the base
+  pub(crate) frame_allocs: Vec<(usize, usize)>,
+}
+
+impl Checkpoint {
+  /// The number of dynamic instructions that had been executed when the checkpoint was taken
+  #[must_use]
+  pub const fn instruction_count(&self) -> u32 {
+    self.instruction_count
+  }
+
+  /// Serializes the checkpoint into a byte buffer
+  #[must_use]
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    self
+      .write_to(&mut bytes)
+      .expect("writing to a Vec should not fail");
+    bytes
+  }
+
+  /// Deserializes a checkpoint from a buffer created by [`Checkpoint::to_bytes`]
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, InterpError> {
+    Self::read_from(bytes)
+  }
+
+  /// Writes the checkpoint to the file at ```path```, replacing its contents if it already exists
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), InterpError> {
+    File::create(path)
+      .and_then(|f| self.write_to(BufWriter::new(f)))
+      .map_err(|e| InterpError::IoError(Box::new(e)))
+  }
+
+  /// Reads a checkpoint from a file written by [`Checkpoint::save`]
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, InterpError> {
+    let f = File::open(path).map_err(|e| InterpError::IoError(Box::new(e)))?;
+    Self::read_from(BufReader::new(f))
+  }
+
+  /// Serializes the checkpoint to ```w```
+  pub fn write_to<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+    writeln!(w, "{CHECKPOINT_HEADER}")?;
+    writeln!(w, "instructions {}", self.instruction_count)?;
+    writeln!(w, "unique_ids {}", self.unique_ids)?;
+    for f in &self.frames {
+      writeln!(
+        w,
+        "frame {} {} {} {} {}",
+        f.func,
+        f.block,
+        f.instr,
+        f.last_label.as_deref().unwrap_or("-"),
+        f.dest.map_or_else(|| "-".to_string(), |d| d.to_string())
+      )?;
+    }
+    for (pointer, size) in &self.env.stack_pointers {
+      writeln!(w, "stack {pointer} {size}")?;
+    }
+    for (depth, base) in &self.frame_allocs {
+      writeln!(w, "alloca {depth} {base}")?;
+    }
+    writeln!(
+      w,
+      "current {} {}",
+      self.env.current_pointer, self.env.current_frame_size
+    )?;
+    writeln!(w, "env {}", self.env.env.len())?;
+    for v in &self.env.env {
+      write_value(&mut w, v)?;
+    }
+    writeln!(w, "heap {}", self.heap.base_num_counter)?;
+    write_heap(&mut w, &self.heap)?;
+    writeln!(w, "end")?;
+    w.flush()
+  }
+
+  /// Deserializes a checkpoint previously written with [`Checkpoint::write_to`]
+  pub fn read_from<R: BufRead>(r: R) -> Result<Self, InterpError> {
+    let mut lines = Lines::new(r)?;
+    if lines.next_line()? != CHECKPOINT_HEADER {
+      return Err(InterpError::BadCheckpoint(format!(
+        "expected the header `{CHECKPOINT_HEADER}`"
+      )));
+    }
+    let instruction_count = lines.expect("instructions")?[0].parse().map_err(bad)?;
+    let unique_ids = lines.expect("unique_ids")?[0].parse().map_err(bad)?;
+
+    let mut frames = Vec::new();
+    let mut stack_pointers = Vec::new();
+    let mut frame_allocs = Vec::new();
+    let current = loop {
+      let line = lines.next_line()?;
+      let words: Vec<&str> = line.split_whitespace().collect();
+      match words.as_slice() {
+        ["frame", func, block, instr, last_label, dest] => frames.push(Frame {
+          func: (*func).to_string(),
+          block: block.parse().map_err(bad)?,
+          instr: instr.parse().map_err(bad)?,
+          last_label: optional(last_label, |l| Ok(l.to_string()))?,
+          dest: optional(dest, |d| d.parse().map_err(bad))?,
+        }),
+        ["stack", pointer, size] => {
+          stack_pointers.push((pointer.parse().map_err(bad)?, size.parse().map_err(bad)?));
+        }
+        ["alloca", depth, base] => {
+          frame_allocs.push((depth.parse().map_err(bad)?, base.parse().map_err(bad)?));
+        }
+        ["current", pointer, size] => {
+          break (
+            pointer.parse::<usize>().map_err(bad)?,
+            size.parse::<usize>().map_err(bad)?,
+          )
+        }
+        _ => {
+          return Err(InterpError::BadCheckpoint(format!(
+            "unexpected line `{line}`"
+          )))
+        }
+      }
+    };
+    if frames.is_empty() {
+      return Err(InterpError::BadCheckpoint("no frames found".to_string()));
+    }
+
+    let env_len: usize = lines.expect("env")?[0].parse().map_err(bad)?;
+    let env = (0..env_len)
+      .map(|_| read_value(&lines.next_line()?))
+      .collect::<Result<Vec<_>, _>>()?;
+    if current.0 + current.1 > env.len() {
+      return Err(InterpError::BadCheckpoint(
+        "the current frame is outside of the environment".to_string(),
+      ));
+    }
+
+    let mut heap = Heap {
+      base_num_counter: lines.expect("heap")?[0].parse().map_err(bad)?,
+      ..Heap::default()
+    };
+    loop {
+      let line = lines.next_line()?;
+      let words: Vec<&str> = line.split_whitespace().collect();
+      match words.as_slice() {
+        ["block", base, len] => {
+          let base: usize = base.parse().map_err(bad)?;
+          let len: usize = len.parse().map_err(bad)?;
+          let block = (0..len)
+            .map(|_| read_value(&lines.next_line()?))
+            .collect::<Result<Vec<_>, _>>()?;
+          heap.memory.insert(base, block);
+        }
+        ["end"] => break,
+        _ => {
+          return Err(InterpError::BadCheckpoint(format!(
+            "unexpected line `{line}`"
+          )))
+        }
+      }
+    }
+
+    Ok(Self {
+      instruction_count,
+      unique_ids,
+      frames,
+      env: Environment {
+        current_pointer: current.0,
+        current_frame_size: current.1,
+        stack_pointers,
+        env,
+        poison: false,
+      },
+      heap,
+      frame_allocs,
+    })
+  }
+}
+
+// Writes every allocation in `heap`, in order of their bases
+pub(crate) fn write_heap<W: Write>(w: &mut W, heap: &Heap) -> std::io::Result<()> {
+  for (base, block) in sorted_entries(&heap.memory) {
+    writeln!(w, "block {base} {}", block.len())?;
+    for v in block {
+      write_value(w, v)?;
+    }
+  }
+  Ok(())
+}
+
+fn write_value<W: Write>(w: &mut W, v: &Value) -> std::io::Result<()> {
+  match v {
+    Value::Int(i) => writeln!(w, "int {i}"),
+    Value::Bool(b) => writeln!(w, "bool {b}"),
+    Value::Float(f) => writeln!(w, "float {:x}", f.to_bits()),
+    Value::Pointer(Pointer { base, offset }) => writeln!(w, "ptr {base} {offset}"),
+    Value::Uninitialized => writeln!(w, "uninit"),
+  }
+}
+
+fn read_value(line: &str) -> Result<Value, InterpError> {
+  let words: Vec<&str> = line.split_whitespace().collect();
+  Ok(match words.as_slice() {
+    ["int", i] => Value::Int(i.parse().map_err(bad)?),
+    ["bool", b] => Value::Bool(b.parse().map_err(bad)?),
+    ["float", bits] => Value::Float(f64::from_bits(u64::from_str_radix(bits, 16).map_err(bad)?)),
+    ["ptr", base, offset] => Value::Pointer(Pointer {
+      base: base.parse().map_err(bad)?,
+      offset: offset.parse().map_err(bad)?,
+    }),
+    ["uninit"] => Value::Uninitialized,
+    _ => {
+      return Err(InterpError::BadCheckpoint(format!(
+        "expected a value, found `{line}`"
+      )))
+    }
+  })
+}
+
+// `-` is used to mark a missing label or destination
+fn optional<T>(
+  word: &str,
+  f: impl FnOnce(&str) -> Result<T, InterpError>,
+) -> Result<Option<T>, InterpError> {
+  if word == "-" {
+    Ok(None)
+  } else {
+    f(word).map(Some)
+  }
+}
+
+fn bad<E: std::fmt::Display>(e: E) -> InterpError {
+  InterpError::BadCheckpoint(e.to_string())
+}
+
+struct Lines {
+  lines: std::vec::IntoIter<String>,
+}
+
+impl Lines {
+  fn new<R: BufRead>(r: R) -> Result<Self, InterpError> {
+    Ok(Self {
+      lines: r
+        .lines()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| InterpError::IoError(Box::new(e)))?
+        .into_iter(),
+    })
+  }
+
+  fn next_line(&mut self) -> Result<String, InterpError> {
+    self
+      .lines
+      .next()
+      .ok_or_else(|| InterpError::BadCheckpoint("unexpected end of checkpoint".to_string()))
+  }
+
+  // Reads a line of the form `key arg` and returns the arguments
+  fn expect(&mut self, key: &str) -> Result<Vec<String>, InterpError> {
+    let line = self.next_line()?;
+    let mut words = line.split_whitespace();
+    match words.next() {
+      Some(k) if k == key => {
+        let args: Vec<String> = words.map(str::to_string).collect();
+        if args.is_empty() {
+          Err(InterpError::BadCheckpoint(format!(
+            "missing value for `{key}`"
+          )))
+        } else {
+          Ok(args)
+        }
+      }
+      _ => Err(InterpError::BadCheckpoint(format!(
+        "expected `{key}`, found `{line}`"
+      ))),
+    }
+  }
+}
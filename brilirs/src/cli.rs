@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{ArgEnum, Parser};
 
 #[derive(Parser)]
 #[clap(about, version, author)] // keeps the cli synced with Cargo.toml
@@ -8,6 +8,18 @@ pub struct Cli {
   #[clap(short, long)]
   pub profile: bool,
 
+  /// With --profile, only count the instructions executed in the function of this name
+  #[clap(long)]
+  pub profile_function: Option<String>,
+
+  /// With --profile-function, also count the instructions executed in the functions that it calls
+  #[clap(long)]
+  pub profile_callees: bool,
+
+  /// With --profile, also count the id instructions that assign a variable to itself
+  #[clap(long)]
+  pub self_ids: bool,
+
   /// The bril file to run. stdin is assumed if file is not provided
   #[clap(short, long)]
   pub file: Option<String>,
@@ -16,10 +28,315 @@ pub struct Cli {
   #[clap(short, long)]
   pub check: bool,
 
+  /// Flag to also validate that the bril program is in strict SSA form where every variable is assigned at most once
+  #[clap(long)]
+  pub ssa: bool,
+
   /// Flag for when the bril program is in text form
   #[clap(short, long)]
   pub text: bool,
 
+  /// Flag to output the return value of every function call in the order that the calls were made
+  #[clap(long)]
+  pub capture_returns: bool,
+
+  /// Flag to output the number of instructions executed under each call stack in the folded stack format used by flamegraph.pl
+  #[clap(long)]
+  pub flamegraph: bool,
+
+  /// With --flamegraph, sample the call stack once every this many instructions instead of counting every instruction
+  #[clap(long, requires = "flamegraph")]
+  pub flamegraph_interval: Option<std::num::NonZeroU64>,
+
+  /// Flag to output, for each function, the most distinct variables written during a single call as an approximation of register pressure
+  #[clap(long)]
+  pub live_slots: bool,
+
+  /// Flag to allow passing an int to a function argument of type float, where it is converted to a float
+  #[clap(long)]
+  pub relaxed_calls: bool,
+
+  /// Flag to output the parsed arguments of the main function and their types before execution
+  #[clap(long)]
+  pub echo_args: bool,
+
+  /// Flag to report an error when integer arithmetic overflows instead of wrapping around
+  #[clap(long)]
+  pub checked: bool,
+
+  /// Operations which report an error when they overflow, whether or not --checked is given. An operation in both lists is checked
+  #[clap(long, use_value_delimiter = true, arg_enum)]
+  pub checked_ops: Vec<ArithmeticOp>,
+
+  /// Operations which wrap around when they overflow, whether or not --checked is given
+  #[clap(long, use_value_delimiter = true, arg_enum)]
+  pub wrapping_ops: Vec<ArithmeticOp>,
+
+  /// Flag to warn about any printed value that does not parse back into the same value
+  #[clap(long)]
+  pub roundtrip_check: bool,
+
+  /// Flag to print booleans as 1 and 0 instead of true and false
+  #[clap(long)]
+  pub numeric_bools: bool,
+
+  /// Flag to prefix each printed line with the number of instructions executed so far
+  #[clap(long)]
+  pub print_inst_count: bool,
+
+  /// Flag to output the contents of the allocation when a `load` reads uninitialized memory
+  #[clap(long)]
+  pub dump_uninit_reads: bool,
+
+  /// Flag to output the enabled metrics as one JSON object instead of their text output
+  #[clap(long)]
+  pub json_summary: bool,
+
+  /// Flag to output every call as begin and end events in the Chrome tracing JSON format, timed by instruction count
+  #[clap(long)]
+  pub chrome_trace: bool,
+
+  /// Flag to output the number of loads that read a cell which was already loaded and has not been stored to since
+  #[clap(long)]
+  pub redundant_loads: bool,
+
+  /// Flag to output the number of loads that read a cell whose last access was a store to it
+  #[clap(long)]
+  pub store_forwardable_loads: bool,
+
+  /// Flag to report an error when `free` does not release the most recent allocation that is still live
+  #[clap(long)]
+  pub lifo_free: bool,
+
+  /// Flag to report an error when a value is stored to an allocation that already holds values of another type
+  #[clap(long)]
+  pub typed_heap: bool,
+
+  /// Flag to output the variables of the failing call and the contents of the heap when execution stops with an error
+  #[clap(long)]
+  pub dump_on_error: bool,
+
+  /// Flag to output a line for every alloc, dup, free, load and store as it happens, along with its position in the source
+  #[clap(long)]
+  pub trace_memory: bool,
+
+  /// Flag to output a compact line for every heap cell that is read or written, `R base offset` or `W base offset`, for cache simulation
+  #[clap(long)]
+  pub access_log: bool,
+
+  /// Flag to output the function and block each time execution enters a basic block
+  #[clap(long)]
+  pub trace_blocks: bool,
+
+  /// Flag to output each instruction and the value that it assigned as it is executed
+  #[clap(long)]
+  pub trace_instructions: bool,
+
+  /// The rounding mode of float arithmetic
+  #[clap(long, default_value = "nearest", arg_enum)]
+  pub rounding: Rounding,
+
+  /// How integer division rounds: toward zero like C, or toward negative infinity like Python's `//`
+  #[clap(long, default_value = "truncating", arg_enum)]
+  pub division: Division,
+
+  /// How a comparison handles an int and a float in a program that is not type checked: report an error, or compare them by their values
+  #[clap(long, default_value = "strict", arg_enum)]
+  pub comparison: Comparison,
+
+  /// Flag to output a warning for every `add`, `sub` or `mul` that wraps around
+  #[clap(long)]
+  pub warn_wraparound: bool,
+
+  /// Flush the output after every this many lines that are printed instead of after each one
+  #[clap(long, default_value = "1")]
+  pub flush_every: std::num::NonZeroU32,
+
+  /// When to flush the output: after every line, or only once the program stops, which is faster for programs that print a lot but lets the output fall behind what is written to stderr
+  #[clap(long, default_value = "line", arg_enum)]
+  pub output_buffering: OutputBuffering,
+
+  /// Output at most this many lines with `print` in each window of `--print-window` instructions
+  #[clap(long)]
+  pub print_limit: Option<u32>,
+
+  /// The number of instructions in each window of `--print-limit`
+  #[clap(long, default_value = "1000000")]
+  pub print_window: std::num::NonZeroU32,
+
+  /// What `--print-limit` does with the lines over the limit: drop them, or hold them back and output them later
+  #[clap(long, default_value = "drop", arg_enum)]
+  pub print_throttle: PrintThrottle,
+
+  /// What to do with an integer literal in the program or its arguments that does not fit in an int: report an error, or wrap around
+  #[clap(long, default_value = "error", arg_enum)]
+  pub int_literals: IntLiterals,
+
+  /// Flag to report an error when `ptradd` creates a pointer with a negative offset instead of waiting until it is used
+  #[clap(long)]
+  pub strict_pointer_offsets: bool,
+
+  /// Flag to report an error when `ptradd` creates a pointer more than one past the end of its allocation instead of waiting until it is used
+  #[clap(long)]
+  pub strict_pointer_bounds: bool,
+
+  /// Flag to report an error when a value produced by `undef` is used instead of treating it as the default value of its type
+  #[clap(long)]
+  pub strict_undef: bool,
+
+  /// Flag to report an error for any use of an undefined value, whether from an unassigned variable, an uninitialized heap cell, or `undef`
+  #[clap(long)]
+  pub strict_uninit: bool,
+
+  /// Flag to make the variables of a call uninitialized when it returns, to catch the interpreter reading them afterwards
+  #[clap(long)]
+  pub poison_frames: bool,
+
+  /// Flag to output where each printed variable was last assigned
+  #[clap(long)]
+  pub print_provenance: bool,
+
+  /// Flag to warn when an allocation returned by a call is freed while it is still reachable
+  #[clap(long)]
+  pub dangling_returns: bool,
+
+  /// Refuse to run a program with more than this many basic blocks in total
+  #[clap(long)]
+  pub max_blocks: Option<usize>,
+
+  /// Run the program once before the run that is measured so that allocations and caches are warmed up. Everything output by the first run, including what it prints and the results of --profile and the other reports, is discarded
+  #[clap(long)]
+  pub warm_up: bool,
+
+  /// Output the program as it is represented by brilirs, after any transformations such as --constant-fold, instead of executing it
+  #[clap(long)]
+  pub dump_program: bool,
+
+  /// Step through the program with commands read from stdin: step, continue, print <var>, break <line>, bt, and back to go back to before the last instruction that was run, which runs the program again from the start up to there. The program needs to be given with --file. Checkpoints are not supported while debugging
+  #[clap(long)]
+  pub debug: bool,
+
+  /// Flag to fold the constants of the program before running it
+  #[clap(long)]
+  pub constant_fold: bool,
+
+  /// Stop with an error when a single allocation would have more than this many entries
+  #[clap(long)]
+  pub max_alloc_size: Option<u64>,
+
+  /// Stop with an error after this many transitions between basic blocks, i.e. to catch infinite loops
+  #[clap(long)]
+  pub max_block_transitions: Option<u64>,
+
+  /// Flag to track values derived from the arguments of the main function and report an error when a tainted pointer is dereferenced
+  #[clap(long)]
+  pub taint: bool,
+
+  /// Flag to output every assignment whose value is never read during execution
+  #[clap(long)]
+  pub dead_stores: bool,
+
+  /// Flag to output which lines of the program were executed in the LCOV format for coverage tools
+  #[clap(long)]
+  pub lcov: bool,
+
+  /// Flag to output how many of the allocations made by each `alloc`, `dup` and `memdup` instruction were freed and leaked
+  #[clap(long)]
+  pub alloc_sites: bool,
+
+  /// The format of the output of `-p`: the total number of instructions, or the number of instructions with each opcode as CSV
+  #[clap(long, default_value = "text", arg_enum)]
+  pub profile_format: ProfileFormat,
+
+  /// Flag to report an error when a pure function is called with the same arguments as a call of it that has not returned yet
+  #[clap(long)]
+  pub detect_infinite_recursion: bool,
+
+  /// Flag to report an error when a pointer to memory from `alloca` is stored into memory that outlives it
+  #[clap(long)]
+  pub check_escapes: bool,
+
+  /// Flag to output how many allocations `alloc` made of each size
+  #[clap(long)]
+  pub alloc_sizes: bool,
+
+  /// Flag to output every variable that held values of more than one type during execution
+  #[clap(long)]
+  pub dynamic_types: bool,
+
+  /// Suspend execution at the first basic block boundary after this many dynamic instructions and write a checkpoint to `--checkpoint-file`
+  #[clap(long, requires = "checkpoint-file")]
+  pub checkpoint_at: Option<u32>,
+
+  /// Where to write the checkpoint taken with `--checkpoint-at`
+  #[clap(long)]
+  pub checkpoint_file: Option<String>,
+
+  /// Resume execution from a checkpoint written by `--checkpoint-at`. The same bril program must be provided
+  #[clap(long)]
+  pub restore: Option<String>,
+
   /// Arguments for the main function
   pub args: Vec<String>,
 }
+
+/// An operation given to `--checked-ops` or `--wrapping-ops`
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArithmeticOp {
+  Add,
+  Sub,
+  Mul,
+  Gcd,
+  Lcm,
+}
+
+/// The values of `--rounding`
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+  Nearest,
+  TowardZero,
+  TowardPositive,
+  TowardNegative,
+}
+
+/// The values of `--division`
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Division {
+  Truncating,
+  Flooring,
+}
+
+/// The values of `--comparison`
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+  Strict,
+  Numeric,
+}
+
+/// The values of `--output-buffering`
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputBuffering {
+  Line,
+  End,
+}
+
+/// The values of `--print-throttle`
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrintThrottle {
+  Drop,
+  Block,
+}
+
+/// The values of `--int-literals`
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntLiterals {
+  Error,
+  Wrap,
+}
+
+/// The values of `--profile-format`
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileFormat {
+  Text,
+  Csv,
+}
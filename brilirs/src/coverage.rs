@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use fxhash::FxHashMap;
+
+use crate::basic_block::{BBFunction, BBProgram};
+
+// Counts how many times each basic block was entered and each function was called, for reporting which lines of the source were executed
+#[derive(Debug, Default)]
+pub struct Coverage<'a> {
+  blocks: FxHashMap<(&'a str, usize), u64>,
+  calls: FxHashMap<&'a str, u64>,
+}
+
+impl<'a> Coverage<'a> {
+  // Called when a call of `func` starts
+  pub fn enter_function(&mut self, func: &'a BBFunction) {
+    *self.calls.entry(&func.name).or_default() += 1;
+  }
+
+  // Called when the block `block` of `func` is entered. Every instruction in it is counted as executed
+  pub fn enter_block(&mut self, func: &'a BBFunction, block: usize) {
+    *self.blocks.entry((&func.name, block)).or_default() += 1;
+  }
+
+  // A single LCOV record for `source`, the file that `prog` was parsed from. Every line of `prog` with an instruction on it is listed with the number of times that the line was executed, which is the most times that any one instruction on it was, so that lines which were never executed are listed with 0
+  pub fn lcov(&self, prog: &BBProgram, source: &str) -> String {
+    let mut lines: BTreeMap<u64, u64> = BTreeMap::new();
+    let mut funcs: Vec<(u64, &str, u64)> = Vec::new();
+    for func in &prog.func_index {
+      if let Some(pos) = func.pos {
+        let calls = self.calls.get(func.name.as_str()).copied().unwrap_or(0);
+        funcs.push((pos.row, &func.name, calls));
+      }
+      for (i, block) in func.blocks.iter().enumerate() {
+        let count = self
+          .blocks
+          .get(&(func.name.as_str(), i))
+          .copied()
+          .unwrap_or(0);
+        for pos in block
+          .instrs
+          .iter()
+          .filter_map(bril_rs::Instruction::get_pos)
+        {
+          let line = lines.entry(pos.row).or_default();
+          *line = (*line).max(count);
+        }
+      }
+    }
+    funcs.sort_unstable();
+
+    let mut out = String::new();
+    // Writing to a String does not fail
+    writeln!(out, "TN:").unwrap();
+    writeln!(out, "SF:{source}").unwrap();
+    for (line, name, _) in &funcs {
+      writeln!(out, "FN:{line},{name}").unwrap();
+    }
+    for (_, name, calls) in &funcs {
+      writeln!(out, "FNDA:{calls},{name}").unwrap();
+    }
+    writeln!(out, "FNF:{}", funcs.len()).unwrap();
+    writeln!(out, "FNH:{}", funcs.iter().filter(|f| f.2 > 0).count()).unwrap();
+    for (line, count) in &lines {
+      writeln!(out, "DA:{line},{count}").unwrap();
+    }
+    writeln!(out, "LF:{}", lines.len()).unwrap();
+    writeln!(out, "LH:{}", lines.values().filter(|c| **c > 0).count()).unwrap();
+    writeln!(out, "end_of_record").unwrap();
+    out
+  }
+}
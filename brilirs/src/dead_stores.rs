@@ -0,0 +1,101 @@
+use bril_rs::Position;
+use serde::Serialize;
+
+use crate::basic_block::{BBFunction, NumifiedInstruction};
+use crate::interp::{at_pos, Environment, JsonPos};
+use crate::sites::Sites;
+
+// A runtime approximation of dead stores. Every instruction with a destination is an assignment site and every variable slot remembers the site that last wrote to it. An argument of an instruction marks the site which wrote its current value as read.
+// A site is reported when none of the values that it wrote were ever read. A site that is read in some executions but not others is not reported
+#[derive(Debug, Default)]
+pub struct DeadStores<'a> {
+  // Parallel to `Environment::env`. The site that last wrote to each slot, if any
+  env: Vec<Option<usize>>,
+  sites: Sites<'a, Site<'a>>,
+}
+
+#[derive(Debug)]
+struct Site<'a> {
+  func: &'a str,
+  var: &'a str,
+  pos: Option<Position>,
+  read: bool,
+}
+
+// A dead store as it is written in the JSON summary
+#[derive(Serialize)]
+pub struct JsonDeadStore<'a> {
+  function: &'a str,
+  variable: &'a str,
+  #[serde(flatten)]
+  pos: Option<JsonPos>,
+}
+
+impl<'a> DeadStores<'a> {
+  fn slot(&mut self, env: &Environment, var: usize) -> &mut Option<usize> {
+    let idx = env.current_pointer + var;
+    if idx >= self.env.len() {
+      self.env.resize(env.env.len().max(idx + 1), None);
+    }
+    &mut self.env[idx]
+  }
+
+  // Called once the frame of `func` has been pushed. Clears anything left over in the frame from an earlier call. The arguments were not written by a site in this function
+  pub fn enter_function(&mut self, env: &Environment, func: &BBFunction) {
+    (0..func.num_of_vars).for_each(|v| *self.slot(env, v) = None);
+  }
+
+  // Called before an instruction is executed to mark the values of its arguments as read
+  pub fn before(&mut self, env: &Environment, numified: &NumifiedInstruction) {
+    for a in &numified.args {
+      if let Some(site) = *self.slot(env, *a) {
+        self.sites[site].read = true;
+      }
+    }
+  }
+
+  // Called after the instruction at `block`, `instr` of `func` has been executed successfully
+  pub fn after(
+    &mut self,
+    env: &Environment,
+    func: &'a BBFunction,
+    block: usize,
+    instr: usize,
+    numified: &NumifiedInstruction,
+    pos: Option<Position>,
+  ) {
+    if let Some(dest) = numified.dest {
+      let site = self.sites.id(&func.name, block, instr, || Site {
+        func: &func.name,
+        var: &func.var_names[dest],
+        pos,
+        read: false,
+      });
+      *self.slot(env, dest) = Some(site);
+    }
+  }
+
+  // A line for each site whose values were never read
+  pub fn report(&self) -> Vec<String> {
+    self
+      .sites
+      .iter()
+      .filter(|s| !s.read)
+      .map(|s| format!("dead store: `{}` in @{}{}", s.var, s.func, at_pos(s.pos)))
+      .collect()
+  }
+
+  // The same as `report` but for the JSON summary
+  pub fn json(&self) -> Vec<JsonDeadStore<'a>> {
+    self
+      .sites
+      .iter()
+      .filter(|s| !s.read)
+      .map(|s| JsonDeadStore {
+        function: s.func,
+        variable: s.var,
+        pos: s.pos.map(JsonPos::from),
+      })
+      .collect()
+  }
+}
@@ -0,0 +1,206 @@
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+
+use bril_rs::Position;
+
+use crate::basic_block::BBProgram;
+use crate::error::PositionalInterpError;
+use crate::interp::{self, at_pos, Breakpoint, InterpOptions, Resume};
+
+// A command line debugger which runs a program with `run`. It stops before the first instruction and then reads one command per line from `commands`:
+//   step             run the next instruction and stop again
+//   continue         run until a breakpoint is reached
+//   print <var>      show the value of a variable in the current frame
+//   break <line>     stop before every instruction on a line of the source
+//   bt               show each call on the stack, innermost first
+//   back             go back to before the instruction that was run last
+// Everything it outputs goes to `out` so that it is kept apart from what the program prints. Once `commands` runs out the program runs to the end
+//
+// `back` undoes the last instruction by running the program again from the start and stopping one instruction earlier than before. This relies on the program doing the same thing every time that it is run, which is only not the case for a program that uses `clock`. Nothing is kept while the program runs forward, so `continue` does not pay for going back
+pub struct Debugger<R, W> {
+  commands: R,
+  out: W,
+  stepping: bool,
+  breakpoints: BTreeSet<u64>,
+  // The number of instructions that this run of the program has reached, which is the index of the one that it is about to run
+  steps: u64,
+  // Where to stop while going back. The instructions before it are run without stopping since they were already stepped through by an earlier run
+  target: Option<u64>,
+  // Set by `back` to the instruction to go back to, which ends this run of the program so that it can be run again up to there
+  rewind: Option<u64>,
+}
+
+fn location(func: &str, pos: Option<Position>) -> String {
+  format!("@{func}{}", at_pos(pos))
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+  pub const fn new(commands: R, out: W) -> Self {
+    Self {
+      commands,
+      out,
+      stepping: true,
+      breakpoints: BTreeSet::new(),
+      steps: 0,
+      target: None,
+      rewind: None,
+    }
+  }
+
+  // Runs "main" of `prog` under the debugger, running it again from the start each time that it goes back
+  pub fn run<T: Write, U: Write>(
+    &mut self,
+    prog: &BBProgram,
+    out: T,
+    input_args: &[String],
+    profiling_out: U,
+    options: &InterpOptions,
+  ) -> Result<(), PositionalInterpError> {
+    let rewinding = Cell::new(false);
+    let mut out = Replay::new(out, &rewinding);
+    let mut profiling_out = Replay::new(profiling_out, &rewinding);
+    loop {
+      let result = interp::execute_main_stepping(
+        prog,
+        &mut out,
+        input_args,
+        &mut profiling_out,
+        options,
+        &mut |b| {
+          let resume = self.on_step(b);
+          rewinding.set(self.rewind.is_some());
+          resume
+        },
+      );
+      match self.rewind.take() {
+        Some(step) => {
+          self.target = Some(step);
+          self.steps = 0;
+          rewinding.set(false);
+          out.restart();
+          profiling_out.restart();
+        }
+        None => return result,
+      }
+    }
+  }
+
+  // Called before every instruction
+  fn on_step(&mut self, b: &Breakpoint) -> Resume {
+    let step = self.steps;
+    self.steps += 1;
+    let arrived = match self.target {
+      Some(target) if step < target => return Resume::Continue,
+      Some(_) => {
+        self.target = None;
+        true
+      }
+      None => false,
+    };
+    let at_breakpoint = b.pos.is_some_and(|p| self.breakpoints.contains(&p.row));
+    if arrived || self.stepping || at_breakpoint {
+      // The debugger's own output is best effort, a failure to write it should not stop the program
+      let _ = self.prompt(b, step);
+    }
+    if self.rewind.is_some() {
+      Resume::Abort
+    } else {
+      Resume::Continue
+    }
+  }
+
+  fn prompt(&mut self, b: &Breakpoint, step: u64) -> std::io::Result<()> {
+    writeln!(self.out, "stopped in {}", location(b.func, b.pos))?;
+    let mut line = String::new();
+    loop {
+      line.clear();
+      if self.commands.read_line(&mut line)? == 0 {
+        self.stepping = false;
+        self.breakpoints.clear();
+        return Ok(());
+      }
+      let mut words = line.split_whitespace();
+      match (words.next(), words.next()) {
+        (None, _) => {}
+        (Some("step"), None) => {
+          self.stepping = true;
+          return Ok(());
+        }
+        (Some("continue"), None) => {
+          self.stepping = false;
+          return Ok(());
+        }
+        (Some("back"), None) => {
+          if step > 0 {
+            self.rewind = Some(step - 1);
+            return Ok(());
+          }
+          writeln!(self.out, "there is no earlier instruction")?;
+        }
+        (Some("print"), Some(var)) => {
+          let message = match b.vars().into_iter().find(|(name, _)| *name == var) {
+            Some((name, value)) => format!("{name} = {value}"),
+            None => format!("`{var}` does not have a value"),
+          };
+          writeln!(self.out, "{message}")?;
+        }
+        (Some("break"), Some(row)) => match row.parse() {
+          Ok(row) => {
+            self.breakpoints.insert(row);
+            writeln!(self.out, "breakpoint at line {row}")?;
+          }
+          Err(_) => writeln!(self.out, "`{row}` is not a line number")?,
+        },
+        (Some("bt"), None) => {
+          for (i, (func, pos)) in b.backtrace().into_iter().enumerate() {
+            writeln!(self.out, "#{i} {}", location(func, pos))?;
+          }
+        }
+        _ => writeln!(self.out, "unknown command `{}`", line.trim())?,
+      }
+    }
+  }
+}
+
+// Passes on what the program outputs except for what an earlier run of it already wrote, so that running it again to go back does not output anything twice. Nothing is passed on once a run is ending to go back
+struct Replay<'a, W> {
+  inner: W,
+  // The number of bytes output by this run and by the furthest that any run has got
+  position: usize,
+  written: usize,
+  rewinding: &'a Cell<bool>,
+}
+
+impl<'a, W: Write> Replay<'a, W> {
+  const fn new(inner: W, rewinding: &'a Cell<bool>) -> Self {
+    Self {
+      inner,
+      position: 0,
+      written: 0,
+      rewinding,
+    }
+  }
+
+  // Called before the program is run again from the start
+  const fn restart(&mut self) {
+    self.position = 0;
+  }
+}
+
+impl<W: Write> Write for Replay<'_, W> {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    if self.rewinding.get() {
+      return Ok(buf.len());
+    }
+    let seen = self.written.saturating_sub(self.position).min(buf.len());
+    self.inner.write_all(&buf[seen..])?;
+    self.position += buf.len();
+    self.written = self.written.max(self.position);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.inner.flush()
+  }
+}
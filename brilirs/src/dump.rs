@@ -0,0 +1,219 @@
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::basic_block::{BBFunction, BBProgram, BasicBlock, NumifiedInstruction};
+use crate::interp::JsonPos;
+
+// The numbered variables of an instruction, i.e. `dest 2, args 0 1`
+fn numified(n: &NumifiedInstruction) -> String {
+  let mut parts = Vec::new();
+  if let Some(dest) = n.dest {
+    parts.push(format!("dest {dest}"));
+  }
+  if !n.args.is_empty() {
+    let args: Vec<_> = n.args.iter().map(ToString::to_string).collect();
+    parts.push(format!("args {}", args.join(" ")));
+  }
+  parts.join(", ")
+}
+
+fn dump_function(out: &mut String, func: &BBFunction) -> std::fmt::Result {
+  write!(out, "@{}", func.name)?;
+  if !func.args.is_empty() {
+    let args: Vec<_> = func.args.iter().map(ToString::to_string).collect();
+    write!(out, "({})", args.join(", "))?;
+  }
+  if let Some(ty) = &func.return_type {
+    write!(out, ": {ty}")?;
+  }
+  writeln!(out)?;
+  let vars: Vec<_> = func
+    .var_names
+    .iter()
+    .enumerate()
+    .map(|(i, name)| format!("{i} {name}"))
+    .collect();
+  writeln!(out, "  vars: {}", vars.join(", "))?;
+  for (i, block) in func.blocks.iter().enumerate() {
+    write!(out, "  block {i}")?;
+    if let Some(label) = &block.label {
+      write!(out, " .{label}")?;
+    }
+    if !block.exit.is_empty() {
+      let exits: Vec<_> = block.exit.iter().map(ToString::to_string).collect();
+      write!(out, " -> {}", exits.join(" "))?;
+    }
+    writeln!(out)?;
+    for (instr, n) in block.instrs.iter().zip(block.numified_instrs.iter()) {
+      let n = numified(n);
+      if n.is_empty() {
+        writeln!(out, "    {instr}")?;
+      } else {
+        writeln!(out, "    {instr}  # {n}")?;
+      }
+    }
+  }
+  Ok(())
+}
+
+// The JSON forms of the parts of a program, with their fields in the order that they are written
+#[derive(Serialize)]
+struct JsonProgram<'a> {
+  main: Option<usize>,
+  functions: Vec<JsonFunction<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonFunction<'a> {
+  name: &'a str,
+  #[serde(flatten)]
+  pos: Option<JsonPos>,
+  args: Vec<JsonArg<'a>>,
+  return_type: Option<String>,
+  vars: &'a [String],
+  arg_vars: &'a [usize],
+  blocks: Vec<JsonBlock<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonArg<'a> {
+  name: &'a str,
+  #[serde(rename = "type")]
+  arg_type: String,
+}
+
+#[derive(Serialize)]
+struct JsonBlock<'a> {
+  label: Option<&'a str>,
+  exits: &'a [usize],
+  instrs: Vec<JsonInstr<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonInstr<'a> {
+  instr: String,
+  #[serde(flatten)]
+  pos: Option<JsonPos>,
+  dest: Option<usize>,
+  args: &'a [usize],
+  funcs: &'a [usize],
+}
+
+fn json_block(block: &BasicBlock) -> JsonBlock<'_> {
+  JsonBlock {
+    label: block.label.as_deref(),
+    exits: &block.exit,
+    instrs: block
+      .instrs
+      .iter()
+      .zip(block.numified_instrs.iter())
+      .map(|(instr, n)| JsonInstr {
+        instr: instr.to_string(),
+        pos: instr.get_pos().map(JsonPos::from),
+        dest: n.dest,
+        args: &n.args,
+        funcs: &n.funcs,
+      })
+      .collect(),
+  }
+}
+
+fn json_function(func: &BBFunction) -> JsonFunction<'_> {
+  JsonFunction {
+    name: &func.name,
+    pos: func.pos.map(JsonPos::from),
+    args: func
+      .args
+      .iter()
+      .map(|a| JsonArg {
+        name: &a.name,
+        arg_type: a.arg_type.to_string(),
+      })
+      .collect(),
+    return_type: func.return_type.as_ref().map(ToString::to_string),
+    vars: &func.var_names,
+    arg_vars: &func.args_as_nums,
+    blocks: func.blocks.iter().map(json_block).collect(),
+  }
+}
+
+impl BBProgram {
+  /// A textual form of the program as brilirs represents it, meant to be compared against an expected program in tests, such as after [`BBProgram::constant_fold`]
+  ///
+  /// Functions are sorted by name since their order does not change what the program does. Each one is followed by its numbered variables, `vars: 0 n, 1 one`, and then its basic blocks in order, each shown as its index, its label if it has one, and the blocks that it can jump to, i.e. `block 1 .loop -> 1 2`. The instructions of a block are written as Bril text, followed by the numbers of the variables that they write and read, i.e. `# dest 2, args 0 1`. Source positions are left out so that moving code around in a file does not change the output.
+  ///
+  /// The output is deterministic for a given program but it is not meant to be parsed: block boundaries that did not come from a label, such as after a `jmp`, are only visible in the output.
+  ///
+  /// ```
+  /// use brilirs::basic_block::BBProgram;
+  /// use bril_rs::Program;
+  ///
+  /// let src = "@main {\n  a: int = const 6;\n  b: int = const 7;\n  c: int = mul a b;\n  print c;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  ///
+  /// assert_eq!(
+  ///   bbprog
+  ///     .constant_fold(&brilirs::interp::InterpOptions::default())
+  ///     .canonical_dump(),
+  ///   "@main
+  ///   vars: 0 a, 1 b, 2 c
+  ///   block 0
+  ///     a: int = const 6;  # dest 0
+  ///     b: int = const 7;  # dest 1
+  ///     c: int = const 42;  # dest 2
+  ///     print c;  # args 2
+  /// "
+  /// );
+  /// ```
+  #[must_use]
+  pub fn canonical_dump(&self) -> String {
+    let mut funcs: Vec<_> = self.func_index.iter().collect();
+    funcs.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut out = String::new();
+    for func in funcs {
+      // Writing to a String does not fail
+      dump_function(&mut out, func).unwrap();
+    }
+    out
+  }
+
+  /// The program exactly as brilirs represents it, as a single line of JSON, for tools that inspect or check how the basic blocks were built
+  ///
+  /// The object has the index of "main" in `functions`, or `null` if there is no "main", and `functions` in the order that `funcs` below refers to them. Each function has its `name`, `args` with their `name` and `type`, `return_type`, the names of its numbered variables as `vars`, which variables the arguments are stored in as `arg_vars`, and its `blocks` in order. Each block has its `label`, the indices of the blocks that it can jump to as `exits`, and its `instrs`. Each instruction has its Bril text as `instr`, the variable that it writes as `dest` and the variables and functions that it reads as `args` and `funcs`, all by number. Source positions are included as `line` and `column` when the program has them.
+  ///
+  /// ```
+  /// use brilirs::basic_block::BBProgram;
+  /// use bril_rs::Program;
+  ///
+  /// let src = "@main {\n  n: int = const 1;\n  jmp .end;\n.end:\n  m: int = add n n;\n  print m;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  ///
+  /// assert_eq!(
+  ///   bbprog.to_json(),
+  ///   concat!(
+  ///     r#"{"main":0,"functions":[{"name":"main","args":[],"return_type":null,"vars":["n","m"],"arg_vars":[],"blocks":["#,
+  ///     r#"{"label":null,"exits":[1],"instrs":["#,
+  ///     r#"{"instr":"n: int = const 1;","dest":0,"args":[],"funcs":[]},"#,
+  ///     r#"{"instr":"jmp .end;","dest":null,"args":[],"funcs":[]}]},"#,
+  ///     r#"{"label":"end","exits":[],"instrs":["#,
+  ///     r#"{"instr":"m: int = add n n;","dest":1,"args":[0,0],"funcs":[]},"#,
+  ///     r#"{"instr":"print m;","dest":null,"args":[1],"funcs":[]}]}]}]}"#
+  ///   )
+  /// );
+  /// ```
+  #[must_use]
+  pub fn to_json(&self) -> String {
+    let program = JsonProgram {
+      main: self.index_of_main,
+      functions: self.func_index.iter().map(json_function).collect(),
+    };
+    serde_json::to_string(&program).expect("a program has no maps to serialize")
+  }
+}
@@ -10,22 +10,60 @@ pub enum InterpError {
   MemLeak,
   #[error("Trying to load from uninitialized memory")]
   UsingUninitializedMemory,
+  #[error("Trying to load from uninitialized memory at base `{0}`, offset `{1}`")]
+  UsingUninitializedMemoryAt(usize, i64), // (base, offset)
+  #[error("`{0}` was used while it holds an undefined value")]
+  UsingUndefinedValue(String),
   #[error("phi node executed with no last label")]
   NoLastLabel,
   #[error("Could not find label: {0}")]
   MissingLabel(String),
+  #[error("`{0}` was executed outside of a speculation started by this call")]
+  NotSpeculating(String),
+  #[error("Returned from a function while it is still speculating")]
+  ReturnWhileSpeculating,
+  #[error("`{0}` can not be used in a program whose runs are cached")]
+  UncacheableInstruction(String),
   #[error("no main function defined, doing nothing")]
   NoMainFunction,
   #[error("phi node has unequal numbers of labels and args")]
   UnequalPhiNode,
+  #[error("The program has more than the maximum of `{0}` basic blocks")]
+  ProgramTooLarge(usize),
   #[error("multiple functions of the same name found")]
   DuplicateFunction,
-  #[error("Expected empty return for `{0}`, found value")]
-  NonEmptyRetForFunc(String),
+  #[error("{}", return_mismatch_message(.0, .1.as_ref(), .2.as_ref()))]
+  ReturnTypeMismatch(String, Option<bril_rs::Type>, Option<bril_rs::Type>), // (func, declared, returned)
+  #[error("{}", non_empty_ret_message(.0, .1.as_ref()))]
+  NonEmptyRetForFunc(String, Option<bril_rs::Type>), // (function, the type of the value that was found if it is known)
+  #[error("The integer literal `{0}` is outside of the range of an int")]
+  IntegerLiteralOutOfRange(String),
   #[error("cannot allocate `{0}` entries")]
   CannotAllocSize(i64),
+  #[error("cannot allocate `{0}` entries, which is more than the maximum allocation size")]
+  AllocationTooLarge(i64),
+  #[error("Field `{0}` of the record is not of type `{1}`")]
+  BadRecordField(usize, bril_rs::Type), // (field index, expected)
   #[error("Tried to free illegal memory location base: `{0}`, offset: `{1}`. Offset must be 0.")]
   IllegalFree(usize, i64), // (base, offset)
+  #[error("Tried to memdup illegal memory location base: `{0}`, offset: `{1}`. Offset must be 0.")]
+  IllegalMemdup(usize, i64), // (base, offset)
+  #[error(
+    "Freed the allocation with base `{0}` before the more recent allocation with base `{1}`"
+  )]
+  NonLifoFree(usize, usize), // (base, expected base)
+  #[error(
+    "Cannot take the difference of pointers into the allocations with bases `{0}` and `{1}`"
+  )]
+  IncomparablePointers(usize, usize),
+  #[error("Stored a `{2}` into the allocation with base `{0}`, which holds `{1}`")]
+  HeapTypeMismatch(usize, &'static str, &'static str), // (base, expected, found)
+  #[error("Stored a pointer into the allocation with base `{0}`, which is freed when its call returns, into the allocation with base `{1}`, which outlives it")]
+  EscapingStackPointer(usize, usize), // (stored base, base stored into)
+  #[error("ptradd created a pointer with the negative offset `{0}`")]
+  NegativePointerOffset(i64),
+  #[error("ptradd created a pointer with the offset `{1}`, which is more than one past the end of the allocation with base `{0}` of size `{2}`")]
+  PointerPastEnd(usize, i64, usize), // (base, offset, size)
   #[error("Uninitialized heap location `{0}` and/or illegal offset `{1}`")]
   InvalidMemoryAccess(usize, i64), // (base, offset)
   #[error("Expected `{0}` function arguments, found `{1}`")]
@@ -38,6 +76,14 @@ pub enum InterpError {
   BadNumFuncs(usize, usize), // (expected, actual)
   #[error("no function of name `{0}` found")]
   FuncNotFound(String),
+  #[error("`@{0}` was called with the same arguments as a call of it that has not returned yet, so it will never return")]
+  InfiniteRecursionDetected(String),
+  #[error("argument {1} of a call of the native function `@{0}` holds an undefined value")]
+  NativeUndefinedArg(String, usize),
+  #[error("the native function `@{0}` returned a `{1}` for a destination of type `{2}`")]
+  NativeReturnTypeMismatch(String, String, bril_rs::Type), // (func, returned, expected)
+  #[error("`@{0}` was called but it does not have a stub")]
+  UnstubbedCall(String),
   #[error("undefined variable `{0}`")]
   VarUndefined(String),
   #[error("Label `{0}` for phi node not found")]
@@ -48,12 +94,64 @@ pub enum InterpError {
   BadFuncArgType(bril_rs::Type, String), // (expected, actual)
   #[error("Expected type `{0:?}` for assignment, found `{1:?}`")]
   BadAsmtType(bril_rs::Type, bril_rs::Type), // (expected, actual). For when the LHS type of an instruction is bad
+  #[error("`{0}` is assigned more than once, which is not allowed in SSA form")]
+  MultipleDefinitionsInSSA(String),
+  #[error("`{0}` can not compare a `{1}` with a `{2}`")]
+  OperandTypeMismatch(bril_rs::ValueOps, &'static str, &'static str), // (op, arg0, arg1)
+  #[error("Integer overflow in `{0}` with arguments `{1}` and `{2}`")]
+  IntegerOverflow(bril_rs::ValueOps, i64, i64), // (op, arg0, arg1)
+  #[error("Exceeded the limit of `{0}` basic block transitions")]
+  BlockTransitionLimitExceeded(u64),
+  #[error("Timed out after `{0:?}`")]
+  TimedOut(std::time::Duration),
+  #[error("A tainted pointer was used by `{0}`")]
+  TaintViolation(String),
+  #[error("Malformed checkpoint: {0}")]
+  BadCheckpoint(String),
+  #[error("Execution was stopped by a debugger")]
+  AbortedAtBreakpoint,
+  #[error("Execution was suspended to take a checkpoint")]
+  Checkpointed,
   #[error("There has been an io error when trying to print: `{0:?}`")]
   IoError(Box<std::io::Error>),
   #[error("You probably shouldn't see this error, this is here to handle conversions between InterpError and PositionalError")]
   PositionalInterpErrorConversion(#[from] PositionalInterpError),
 }
 
+fn return_mismatch_message(
+  func: &str,
+  declared: Option<&bril_rs::Type>,
+  returned: Option<&bril_rs::Type>,
+) -> String {
+  let returned = returned.map_or_else(|| "nothing".to_string(), |t| format!("`{t}`"));
+  let declared = declared.map_or_else(
+    || "without a return type".to_string(),
+    |t| format!("to return `{t}`"),
+  );
+  format!("`@{func}` is declared {declared} but returns {returned}")
+}
+
+fn non_empty_ret_message(func: &str, found: Option<&bril_rs::Type>) -> String {
+  let found = found.map_or_else(|| "value".to_string(), |t| format!("value of type `{t}`"));
+  if func == "main" {
+    format!("Expected empty return for `main`, found {found}. `main` can not return a value so print it instead")
+  } else {
+    format!("Expected empty return for `{func}`, found {found}")
+  }
+}
+
+/// The kinds of [`PositionalInterpError`] that an embedder may want to handle differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+  /// "main" returns a value or was declared with a return type
+  MainReturnsValue,
+  /// The run was stopped because it took longer than it was allowed to
+  TimedOut,
+  /// Any other error
+  Other,
+}
+
 impl InterpError {
   pub fn add_pos(self, pos: Option<Position>) -> PositionalInterpError {
     match self {
@@ -79,6 +177,34 @@ impl PositionalInterpError {
       pos: None,
     }
   }
+
+  pub(crate) const fn pos(&self) -> Option<Position> {
+    self.pos
+  }
+
+  /// Which kind of error this is
+  ///
+  /// ```
+  /// use brilirs::{basic_block::BBProgram, interp, ErrorKind};
+  /// use bril_rs::Program;
+  ///
+  /// let src = "@main: int { a: int = const 4; ret a; }";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  ///
+  /// let e = interp::execute_main(&bbprog, std::io::sink(), &[], false, std::io::sink()).unwrap_err();
+  /// assert_eq!(e.kind(), ErrorKind::MainReturnsValue);
+  /// ```
+  #[must_use]
+  pub fn kind(&self) -> ErrorKind {
+    match self.e.as_ref() {
+      InterpError::NonEmptyRetForFunc(func, _) if func == "main" => ErrorKind::MainReturnsValue,
+      InterpError::TimedOut(_) => ErrorKind::TimedOut,
+      _ => ErrorKind::Other,
+    }
+  }
 }
 
 impl Display for PositionalInterpError {
@@ -0,0 +1,165 @@
+use bril_rs::{ConstOps, Instruction, Literal, Type, ValueOps};
+
+use crate::basic_block::{BBProgram, NumifiedInstruction};
+use crate::interp::{self, get_arg, DivisionMode, Environment, InterpOptions, RoundingMode, Value};
+use crate::semantics::{self, Semantics};
+
+// The concrete semantics with the rounding and division of the options that the folded program will be run with
+struct FoldSemantics {
+  rounding: RoundingMode,
+  division: DivisionMode,
+}
+
+impl Semantics for FoldSemantics {
+  fn div(&mut self, a: i64, b: i64) -> i64 {
+    interp::divide(self.division, a, b)
+  }
+  fn fadd(&mut self, a: f64, b: f64) -> f64 {
+    interp::fadd(self.rounding, a, b)
+  }
+  fn fsub(&mut self, a: f64, b: f64) -> f64 {
+    interp::fsub(self.rounding, a, b)
+  }
+  fn fmul(&mut self, a: f64, b: f64) -> f64 {
+    interp::fmul(self.rounding, a, b)
+  }
+  fn fdiv(&mut self, a: f64, b: f64) -> f64 {
+    interp::fdiv(self.rounding, a, b)
+  }
+}
+
+// Whether `op` can be folded with `args` without reporting an error that the program would only report if the instruction ran
+fn can_fold(op: ValueOps, env: &Environment, args: &[usize]) -> bool {
+  let int = |i| get_arg::<i64>(env, i, args);
+  match op {
+    ValueOps::Add => int(0).checked_add(int(1)).is_some(),
+    ValueOps::Sub => int(0).checked_sub(int(1)).is_some(),
+    ValueOps::Mul => int(0).checked_mul(int(1)).is_some(),
+    ValueOps::Div => int(0).checked_div(int(1)).is_some(),
+    _ => true,
+  }
+}
+
+impl BBProgram {
+  /// A copy of the program where each value operation whose arguments are all known constants has been replaced by a `const` of its result
+  ///
+  /// The results are computed by the same code as the interpreter with the `rounding` and `division` of ```options``` so the folded program behaves the same as the original when it is run with ```options```. Only the operations listed on [`crate::semantics::Semantics`] are folded. Constants are only tracked within a basic block, and an `add`, `sub`, `mul`, or `div` that would overflow or divide by zero is left as it is so that it still wraps, or reports an error, as the `arithmetic` of ```options``` says when it runs.
+  ///
+  /// ```
+  /// use brilirs::{basic_block::BBProgram, interp};
+  /// use bril_rs::{Instruction, Program};
+  ///
+  /// let src = "@main {\n  a: int = const 6;\n  b: int = const 7;\n  c: int = mul a b;\n  big: int = const 9223372036854775807;\n  d: int = add big a;\n  t: bool = lt a c;\n  print c d t;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  /// let options = interp::InterpOptions::default();
+  /// let folded = bbprog.constant_fold(&options);
+  ///
+  /// // `mul` and `lt` are folded while the overflowing `add` is left alone
+  /// let consts: Vec<bool> = folded.get(0).unwrap().blocks[0]
+  ///   .instrs
+  ///   .iter()
+  ///   .map(|i| matches!(i, Instruction::Constant { .. }))
+  ///   .collect();
+  /// assert_eq!(consts, [true, true, true, true, false, true, false]);
+  ///
+  /// let run = |p: &BBProgram, options: &interp::InterpOptions| {
+  ///   let mut out = Vec::new();
+  ///   interp::execute_main_with(p, &mut out, &[], std::io::sink(), options).unwrap();
+  ///   out
+  /// };
+  /// assert_eq!(run(&bbprog, &options), run(&folded, &options));
+  ///
+  /// // `div` is folded with the division of the options that the program is run with
+  /// let src = "@main {\n  a: int = const -7;\n  b: int = const 2;\n  c: int = div a b;\n  print c;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  /// let flooring = interp::InterpOptions {
+  ///   division: interp::DivisionMode::Flooring,
+  ///   ..Default::default()
+  /// };
+  /// let folded = bbprog.constant_fold(&flooring);
+  /// assert_eq!(run(&folded, &flooring), b"-4\n");
+  /// ```
+  #[must_use]
+  pub fn constant_fold(&self, options: &InterpOptions) -> Self {
+    let mut semantics = FoldSemantics {
+      rounding: options.rounding,
+      division: options.division,
+    };
+    let mut folded = self.clone();
+    for func in &mut folded.func_index {
+      let mut env = Environment::new(func.num_of_vars);
+      for block in &mut func.blocks {
+        // Whether each variable holds a known constant at this point in the block
+        let mut known = vec![false; func.num_of_vars];
+        for (instr, numified) in block
+          .instrs
+          .iter_mut()
+          .zip(block.numified_instrs.iter_mut())
+        {
+          match instr {
+            Instruction::Constant {
+              const_type, value, ..
+            } => {
+              let value = match (const_type, &*value) {
+                (Type::Float, Literal::Int(i)) => Value::Float(*i as f64),
+                (_, value) => Value::from(value),
+              };
+              let dest = numified.dest.unwrap();
+              env.set(dest, value);
+              known[dest] = true;
+            }
+            Instruction::Value {
+              op,
+              dest,
+              op_type,
+              pos,
+              ..
+            } => {
+              let dest_num = numified.dest.unwrap();
+              let result =
+                if numified.args.iter().all(|a| known[*a]) && can_fold(*op, &env, &numified.args) {
+                  semantics::apply(&mut semantics, *op, &env, &numified.args)
+                } else {
+                  None
+                };
+              // `fadd_kahan` also writes to its second argument
+              if let (ValueOps::FaddKahan, Some(a)) = (*op, numified.args.get(1)) {
+                known[*a] = false;
+              }
+              known[dest_num] = result.is_some();
+              if let Some(result) = result {
+                let value = match &result {
+                  Value::Int(i) => Literal::Int(*i),
+                  Value::Bool(b) => Literal::Bool(*b),
+                  Value::Float(f) => Literal::Float(*f),
+                  Value::Pointer(_) | Value::Uninitialized => unreachable!(),
+                };
+                *instr = Instruction::Constant {
+                  dest: std::mem::take(dest),
+                  op: ConstOps::Const,
+                  pos: *pos,
+                  const_type: op_type.clone(),
+                  value,
+                };
+                *numified = NumifiedInstruction {
+                  dest: Some(dest_num),
+                  args: Vec::new(),
+                  funcs: Vec::new(),
+                };
+                env.set(dest_num, result);
+              }
+            }
+            Instruction::Effect { .. } => {}
+          }
+        }
+      }
+    }
+    folded
+  }
+}
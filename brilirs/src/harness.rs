@@ -0,0 +1,262 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::Write;
+
+use crate::basic_block::BBProgram;
+use crate::error::PositionalInterpError;
+use crate::interp::{run_main, Hooks, InterpOptions};
+
+/// Why [`run_expect`] failed
+#[derive(Debug)]
+pub enum Mismatch {
+  /// The program did not run to completion
+  Error(PositionalInterpError),
+  /// The output first differs from what was expected at ```line```, counting from 1. A side is `None` when it has already ended
+  Output {
+    /// The first line that differs
+    line: usize,
+    /// The expected contents of the line
+    expected: Option<String>,
+    /// What the program printed on that line
+    actual: Option<String>,
+  },
+  /// The trace of the instructions that were executed first differs from what was expected at the instruction numbered ```step```, counting from 1. A side is `None` when it has already ended
+  Trace {
+    /// The number of the first instruction that differs
+    step: usize,
+    /// The expected line of the trace
+    expected: Option<String>,
+    /// The line of the trace of the program
+    actual: Option<String>,
+    /// The lines of the trace just before ```step```, which both sides agree on, oldest first
+    context: Vec<String>,
+  },
+}
+
+impl fmt::Display for Mismatch {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Error(e) => write!(f, "{e}"),
+      Self::Output {
+        line,
+        expected,
+        actual,
+      } => {
+        let show = |l: &Option<String>| {
+          l.as_ref()
+            .map_or_else(|| "end of output".to_string(), |l| format!("`{l}`"))
+        };
+        write!(
+          f,
+          "Output differs on line {line}: expected {}, found {}",
+          show(expected),
+          show(actual)
+        )
+      }
+      Self::Trace {
+        step,
+        expected,
+        actual,
+        context,
+      } => {
+        let show = |l: &Option<String>| {
+          l.as_ref()
+            .map_or_else(|| "end of trace".to_string(), |l| format!("`{l}`"))
+        };
+        write!(
+          f,
+          "Trace differs at instruction {step}: expected {}, found {}",
+          show(expected),
+          show(actual)
+        )?;
+        for l in context {
+          write!(f, "\n  after `{l}`")?;
+        }
+        Ok(())
+      }
+    }
+  }
+}
+
+impl std::error::Error for Mismatch {}
+
+/// Runs "main" of ```prog``` with ```inputs``` and checks that it prints ```expected_stdout```
+///
+/// Output is compared line by line so a missing newline at the end is not a mismatch. On success, the number of dynamic instructions that were executed is returned.
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, harness::{run_expect, Mismatch}};
+/// use bril_rs::Program;
+///
+/// let src = "@main(n: int) { one: int = const 1; m: int = add n one; print n; print m; }";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+/// let inputs = ["41".to_string()];
+///
+/// assert_eq!(run_expect(&bbprog, &inputs, "41\n42\n").unwrap(), 4);
+/// match run_expect(&bbprog, &inputs, "41\n43\n") {
+///   Err(Mismatch::Output { line, expected, actual }) => {
+///     assert_eq!(line, 2);
+///     assert_eq!(expected.as_deref(), Some("43"));
+///     assert_eq!(actual.as_deref(), Some("42"));
+///   }
+///   _ => panic!("expected a mismatch"),
+/// }
+/// ```
+pub fn run_expect(
+  prog: &BBProgram,
+  inputs: &[String],
+  expected_stdout: &str,
+) -> Result<u32, Mismatch> {
+  let mut out = Vec::new();
+  let (_, instruction_count) = run_main(
+    prog,
+    &mut out,
+    inputs,
+    std::io::sink(),
+    &InterpOptions::default(),
+    None,
+    Hooks::default(),
+  )
+  .map_err(Mismatch::Error)?;
+
+  let actual = String::from_utf8_lossy(&out);
+  let mut expected_lines = expected_stdout.lines();
+  let mut actual_lines = actual.lines();
+  for line in 1.. {
+    match (expected_lines.next(), actual_lines.next()) {
+      (None, None) => break,
+      (e, a) if e == a => {}
+      (e, a) => {
+        return Err(Mismatch::Output {
+          line,
+          expected: e.map(str::to_string),
+          actual: a.map(str::to_string),
+        })
+      }
+    }
+  }
+  Ok(instruction_count)
+}
+
+// How many of the lines that matched are kept to be shown before a divergence
+const TRACE_CONTEXT: usize = 3;
+
+// Compares the trace as it is written against the expected one, so that the run can be stopped at the first line that differs by failing the write
+struct TraceChecker<'e> {
+  expected: std::str::Lines<'e>,
+  // What has been written since the last newline
+  partial: Vec<u8>,
+  matched: usize,
+  recent: VecDeque<String>,
+  divergence: Option<Mismatch>,
+}
+
+impl TraceChecker<'_> {
+  fn check(&mut self, actual: Option<String>) -> std::io::Result<()> {
+    let expected = self.expected.next().map(str::to_string);
+    if expected == actual {
+      self.matched += 1;
+      if self.recent.len() == TRACE_CONTEXT {
+        self.recent.pop_front();
+      }
+      self.recent.extend(actual);
+      return Ok(());
+    }
+    self.divergence = Some(Mismatch::Trace {
+      step: self.matched + 1,
+      expected,
+      actual,
+      context: self.recent.iter().cloned().collect(),
+    });
+    Err(std::io::Error::other("the trace diverged"))
+  }
+}
+
+impl Write for TraceChecker<'_> {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    for b in buf {
+      if *b == b'\n' {
+        let line = String::from_utf8_lossy(&self.partial).into_owned();
+        self.partial.clear();
+        self.check(Some(line))?;
+      } else {
+        self.partial.push(*b);
+      }
+    }
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
+
+/// Runs "main" of ```prog``` with ```inputs``` and checks that the instructions that it executes are those in ```expected_trace```, stopping at the first one that is not
+///
+/// The trace has a line for each instruction that is executed, in the format of ```InterpOptions::trace_instructions```, so it includes the value assigned by each instruction. A trace recorded from a known good build of a program, such as before an optimization, then catches the first instruction whose behaviour changed even when the output ends up the same. The divergence is reported along with the lines before it. On success, the number of dynamic instructions that were executed is returned.
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, harness::{run_expect_trace, Mismatch}};
+/// use bril_rs::Program;
+///
+/// let src = "@main { a: int = const 2; b: int = const 3; c: int = mul a b; print c; }";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+///
+/// let trace = "@main: a: int = const 2; => 2
+/// @main: b: int = const 3; => 3
+/// @main: c: int = mul a b; => 6
+/// @main: print c;
+/// ";
+/// assert_eq!(run_expect_trace(&bbprog, &[], trace).unwrap(), 4);
+///
+/// match run_expect_trace(&bbprog, &[], &trace.replace("mul a b; => 6", "add a b; => 5")) {
+///   Err(Mismatch::Trace { step, expected, actual, context }) => {
+///     assert_eq!(step, 3);
+///     assert_eq!(expected.as_deref(), Some("@main: c: int = add a b; => 5"));
+///     assert_eq!(actual.as_deref(), Some("@main: c: int = mul a b; => 6"));
+///     assert_eq!(context.len(), 2);
+///   }
+///   _ => panic!("expected a divergence"),
+/// }
+/// ```
+pub fn run_expect_trace(
+  prog: &BBProgram,
+  inputs: &[String],
+  expected_trace: &str,
+) -> Result<u32, Mismatch> {
+  let mut checker = TraceChecker {
+    expected: expected_trace.lines(),
+    partial: Vec::new(),
+    matched: 0,
+    recent: VecDeque::new(),
+    divergence: None,
+  };
+  let options = InterpOptions {
+    trace_instructions: true,
+    ..InterpOptions::default()
+  };
+  let result = run_main(
+    prog,
+    std::io::sink(),
+    inputs,
+    &mut checker,
+    &options,
+    None,
+    Hooks::default(),
+  );
+  if let Some(divergence) = checker.divergence {
+    return Err(divergence);
+  }
+  let (_, instruction_count) = result.map_err(Mismatch::Error)?;
+  // The program finished, so any lines left in the expected trace were never executed
+  checker
+    .check(None)
+    .map_err(|_| checker.divergence.unwrap())?;
+  Ok(instruction_count)
+}
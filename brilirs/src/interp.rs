@@ -1,17 +1,28 @@
 use std::fmt;
 
-use crate::basic_block::{BBFunction, BBProgram, BasicBlock};
+use crate::alloc_sites::AllocSites;
+use crate::basic_block::{opcode, BBFunction, BBProgram, BasicBlock, NumifiedInstruction};
+use crate::checkpoint::{write_heap, Checkpoint, Frame};
+use crate::coverage::Coverage;
+use crate::dead_stores::DeadStores;
 use crate::error::{InterpError, PositionalInterpError};
-use bril_rs::Instruction;
+use crate::key::{canonical_key, ValueKey};
+use crate::native::NativeFunctions;
+use crate::record::FieldValue;
+use crate::semantics::Semantics;
+use crate::taint::Taint;
+use bril_rs::{Instruction, Position};
 
-use fxhash::FxHashMap;
+use fxhash::{FxHashMap, FxHashSet};
+use serde::Serialize;
 
 use mimalloc::MiMalloc;
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
-use std::cmp::max;
+use std::cmp::{max, Ordering};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
 // The Environment is the data structure used to represent the stack of the program.
 // The values of all variables are store here. Each variable is represented as a number so
@@ -23,15 +34,18 @@ use std::cmp::max;
 //  |        Call "foo" pointer(frame size 2)
 //  |        |
 // [a, b, c, a, b]
-struct Environment {
+#[derive(Debug)]
+pub(crate) struct Environment {
   // Pointer into env for the start of the current frame
-  current_pointer: usize,
+  pub(crate) current_pointer: usize,
   // Size of the current frame
-  current_frame_size: usize,
+  pub(crate) current_frame_size: usize,
   // A list of all stack pointers for valid frames on the stack
-  stack_pointers: Vec<(usize, usize)>,
+  pub(crate) stack_pointers: Vec<(usize, usize)>,
   // env is used like a stack. Assume it only grows
-  env: Vec<Value>,
+  pub(crate) env: Vec<Value>,
+  // Whether the slots of a frame are made uninitialized when it is popped
+  pub(crate) poison: bool,
 }
 
 impl Environment {
@@ -43,6 +57,7 @@ impl Environment {
       stack_pointers: Vec::new(),
       // Allocate a larger stack size so the interpreter needs to allocate less often
       env: vec![Value::default(); max(size, 50)],
+      poison: false,
     }
   }
   #[inline(always)]
@@ -85,14 +100,21 @@ impl Environment {
 
   // Remove a frame from the stack
   pub fn pop_frame(&mut self) {
+    if self.poison {
+      // So that reading them before they are written again, which only a bug in numbering the variables can do, reads an uninitialized value instead of what the call that returned left behind
+      self.env[self.current_pointer..self.current_pointer + self.current_frame_size]
+        .fill(Value::Uninitialized);
+    }
     (self.current_pointer, self.current_frame_size) = self.stack_pointers.pop().unwrap();
   }
 }
 
 // todo: This is basically a copy of the heap implement in brili and we could probably do something smarter. This currently isn't that worth it to optimize because most benchmarks do not use the memory extension nor do they run for very long. You (the reader in the future) may be working with bril programs that you would like to speed up that extensively use the bril memory extension. In that case, it would be worth seeing how to implement Heap without a map based memory. Maybe try to re-implement malloc for a large Vec<Value>?
-struct Heap {
-  memory: FxHashMap<usize, Vec<Value>>,
-  base_num_counter: usize,
+/// The [`HeapBackend`] that the interpreter uses unless it is given another one, which keeps each allocation in a map from its base
+#[derive(Debug)]
+pub struct Heap {
+  pub(crate) memory: FxHashMap<usize, Vec<Value>>,
+  pub(crate) base_num_counter: usize,
 }
 
 impl Default for Heap {
@@ -104,17 +126,76 @@ impl Default for Heap {
   }
 }
 
-impl Heap {
-  #[inline(always)]
-  fn is_empty(&self) -> bool {
-    self.memory.is_empty()
+/// Where the memory extension keeps its allocations
+///
+/// The interpreter only goes through this trait to reach the heap, so another implementation, such as one backed by a single flat `Vec` or one that counts every access, can be run in place of [`Heap`] with [`execute_main_with_heap`]. Each use is monomorphized so [`Heap`] is as fast as when it was called directly. The checks that options such as ```lifo_free``` and ```typed_heap``` turn on are done by the interpreter around the backend, so every backend gets them.
+///
+/// A backend gives each allocation a base which is never reused, and a pointer into it is that base along with an offset. Errors are reported with the variants of [`HeapError`] that [`Heap`] uses, such as `InvalidMemoryAccess` for a pointer outside of its allocation.
+pub trait HeapBackend: Default {
+  /// Creates an allocation of ```amount``` uninitialized cells and returns a pointer to its start
+  fn alloc(&mut self, amount: i64, max_size: Option<u64>) -> Result<Value, HeapError>;
+  /// Copies ```amount``` cells starting at ```key``` into a new allocation and returns a pointer to its start
+  fn dup(&mut self, key: &Pointer, amount: i64, max_size: Option<u64>) -> Result<Value, HeapError>;
+  /// Frees the allocation that ```key``` points to the start of
+  fn free(&mut self, key: &Pointer) -> Result<(), HeapError>;
+  /// Reads the cell that ```key``` points to. A pointer can have any offset, such as one past the end of its allocation as in C, but only the cells inside of the allocation can be read or written
+  fn read(&self, key: &Pointer) -> Result<&Value, HeapError>;
+  /// Writes ```val``` to the cell that ```key``` points to
+  fn write(&mut self, key: &Pointer, val: Value) -> Result<(), HeapError>;
+  /// Whether there are no live allocations
+  fn is_empty(&self) -> bool;
+  /// The number of live allocations
+  fn len(&self) -> usize;
+  /// The cells of the live allocation ```base```, including those that are uninitialized
+  fn block(&self, base: usize) -> Option<&[Value]>;
+  /// The bases of the live allocations in increasing order
+  fn bases(&self) -> Vec<usize>;
+
+  /// Outputs which cells of the block that ```key``` points into have been initialized
+  #[cold]
+  fn dump_block<W: std::io::Write>(&self, key: &Pointer, mut w: W) -> Result<(), HeapError> {
+    writeln!(
+      w,
+      "uninitialized read from base {} at offset {}",
+      key.base, key.offset
+    )
+    .and_then(|_| {
+      self
+        .block(key.base)
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .try_for_each(|(i, v)| match v {
+          Value::Uninitialized => writeln!(w, "  {i}: uninitialized"),
+          v => writeln!(w, "  {i}: {v}"),
+        })
+    })
+    .and_then(|_| w.flush())
+    .map_err(|e| InterpError::IoError(Box::new(e)))
   }
+}
 
+/// The error that a [`HeapBackend`] reports
+pub type HeapError = InterpError;
+
+impl Heap {
+  // Checks that an allocation of `amount` cells is allowed
   #[inline(always)]
-  fn alloc(&mut self, amount: i64) -> Result<Value, InterpError> {
+  fn check_alloc_size(amount: i64, max_size: Option<u64>) -> Result<(), InterpError> {
     if amount < 0 {
       return Err(InterpError::CannotAllocSize(amount));
     }
+    if max_size.is_some_and(|max| amount as u64 > max) {
+      return Err(InterpError::AllocationTooLarge(amount));
+    }
+    Ok(())
+  }
+}
+
+impl HeapBackend for Heap {
+  #[inline(always)]
+  fn alloc(&mut self, amount: i64, max_size: Option<u64>) -> Result<Value, InterpError> {
+    Self::check_alloc_size(amount, max_size)?;
     let base = self.base_num_counter;
     self.base_num_counter += 1;
     self
@@ -123,6 +204,28 @@ impl Heap {
     Ok(Value::Pointer(Pointer { base, offset: 0 }))
   }
 
+  fn dup(
+    &mut self,
+    key: &Pointer,
+    amount: i64,
+    max_size: Option<u64>,
+  ) -> Result<Value, InterpError> {
+    Self::check_alloc_size(amount, max_size)?;
+    let region = self
+      .memory
+      .get(&key.base)
+      .and_then(|vec| {
+        let start = usize::try_from(key.offset).ok()?;
+        vec.get(start..start.checked_add(amount as usize)?)
+      })
+      .ok_or(InterpError::InvalidMemoryAccess(key.base, key.offset))?
+      .to_vec();
+    let base = self.base_num_counter;
+    self.base_num_counter += 1;
+    self.memory.insert(base, region);
+    Ok(Value::Pointer(Pointer { base, offset: 0 }))
+  }
+
   #[inline(always)]
   fn free(&mut self, key: &Pointer) -> Result<(), InterpError> {
     if self.memory.remove(&key.base).is_some() && key.offset == 0 {
@@ -155,6 +258,158 @@ impl Heap {
         _ => Ok(val),
       })
   }
+
+  #[inline(always)]
+  fn is_empty(&self) -> bool {
+    self.memory.is_empty()
+  }
+
+  fn len(&self) -> usize {
+    self.memory.len()
+  }
+
+  fn block(&self, base: usize) -> Option<&[Value]> {
+    self.memory.get(&base).map(Vec::as_slice)
+  }
+
+  fn bases(&self) -> Vec<usize> {
+    let mut bases: Vec<_> = self.memory.keys().copied().collect();
+    bases.sort_unstable();
+    bases
+  }
+}
+
+// The heap of a run, which does the checks that the options ask for around whichever backend it is given. Each check only keeps track of anything when it is turned on
+#[derive(Debug, Default)]
+pub(crate) struct CheckedHeap<H: HeapBackend = Heap> {
+  pub(crate) inner: H,
+  // The bases of the live allocations, most recent last. Only tracked when frees have to be in LIFO order
+  live_order: Option<Vec<usize>>,
+  // The type of value held by each allocation that has been stored to, by base. Only tracked when stores have to match
+  element_types: Option<FxHashMap<usize, &'static str>>,
+  // The number of `alloc`s of each size. Only tracked when the sizes are reported
+  pub(crate) alloc_sizes: Option<FxHashMap<usize, u64>>,
+}
+
+impl<H: HeapBackend> CheckedHeap<H> {
+  // `inner` with the checks that `options` turn on, which has to be empty for them to keep track of every allocation
+  fn new(inner: H, options: &InterpOptions) -> Self {
+    Self {
+      inner,
+      live_order: options.lifo_free.then(Vec::new),
+      element_types: options.typed_heap.then(FxHashMap::default),
+      alloc_sizes: options.alloc_sizes.then(FxHashMap::default),
+    }
+  }
+
+  // `inner` without any checks, for a heap that already has allocations that they did not see being made
+  const fn unchecked(inner: H) -> Self {
+    Self {
+      inner,
+      live_order: None,
+      element_types: None,
+      alloc_sizes: None,
+    }
+  }
+
+  // Records the allocation that `res` points to as the most recent
+  #[inline(always)]
+  fn push_live(&mut self, res: &Value) {
+    if let Some(order) = &mut self.live_order {
+      order.push(<&Pointer>::from(res).base);
+    }
+  }
+}
+
+impl<H: HeapBackend> HeapBackend for CheckedHeap<H> {
+  #[inline(always)]
+  fn alloc(&mut self, amount: i64, max_size: Option<u64>) -> Result<Value, InterpError> {
+    let res = self.inner.alloc(amount, max_size)?;
+    self.push_live(&res);
+    if let Some(sizes) = &mut self.alloc_sizes {
+      *sizes.entry(amount as usize).or_default() += 1;
+    }
+    Ok(res)
+  }
+
+  fn dup(
+    &mut self,
+    key: &Pointer,
+    amount: i64,
+    max_size: Option<u64>,
+  ) -> Result<Value, InterpError> {
+    let res = self.inner.dup(key, amount, max_size)?;
+    if let Some(types) = &mut self.element_types {
+      if let Some(ty) = types.get(&key.base).copied() {
+        types.insert(<&Pointer>::from(&res).base, ty);
+      }
+    }
+    self.push_live(&res);
+    Ok(res)
+  }
+
+  #[inline(always)]
+  fn free(&mut self, key: &Pointer) -> Result<(), InterpError> {
+    if let Some(order) = &mut self.live_order {
+      // A free that is illegal for another reason is reported as such by the backend
+      if key.offset == 0 && self.inner.block(key.base).is_some() {
+        let expected = *order.last().unwrap();
+        if expected != key.base {
+          return Err(InterpError::NonLifoFree(key.base, expected));
+        }
+        order.pop();
+      }
+    }
+    self.inner.free(key)?;
+    if let Some(types) = &mut self.element_types {
+      types.remove(&key.base);
+    }
+    Ok(())
+  }
+
+  #[inline(always)]
+  fn write(&mut self, key: &Pointer, val: Value) -> Result<(), InterpError> {
+    if let Some(types) = &mut self.element_types {
+      // Only a store that is in bounds decides the type of the allocation
+      let in_bounds = self
+        .inner
+        .block(key.base)
+        .is_some_and(|block| usize::try_from(key.offset).is_ok_and(|i| i < block.len()));
+      if in_bounds {
+        let expected = *types.entry(key.base).or_insert_with(|| val.type_name());
+        if expected != val.type_name() {
+          return Err(InterpError::HeapTypeMismatch(
+            key.base,
+            expected,
+            val.type_name(),
+          ));
+        }
+      }
+    }
+    self.inner.write(key, val)
+  }
+
+  #[inline(always)]
+  fn read(&self, key: &Pointer) -> Result<&Value, InterpError> {
+    self.inner.read(key)
+  }
+
+  #[inline(always)]
+  fn is_empty(&self) -> bool {
+    self.inner.is_empty()
+  }
+
+  fn len(&self) -> usize {
+    self.inner.len()
+  }
+
+  fn block(&self, base: usize) -> Option<&[Value]> {
+    self.inner.block(base)
+  }
+
+  fn bases(&self) -> Vec<usize> {
+    self.inner.bases()
+  }
 }
 
 // A getter function for when you just want the Value enum
@@ -163,22 +418,76 @@ fn get_value<'a>(vars: &'a Environment, index: usize, args: &[usize]) -> &'a Val
   vars.get(&args[index])
 }
 
+// The ordering of the two arguments of a comparison, which is `None` if either is NaN. Arguments of the type that `op` takes are compared directly and anything else is up to `policy`
+#[inline(always)]
+fn compare_args(
+  policy: ComparisonPolicy,
+  op: bril_rs::ValueOps,
+  vars: &Environment,
+  args: &[usize],
+) -> Result<Option<Ordering>, InterpError> {
+  use bril_rs::ValueOps::{Feq, Fge, Fgt, Fle, Flt};
+  let floats = matches!(op, Feq | Flt | Fgt | Fle | Fge);
+  match (get_value(vars, 0, args), get_value(vars, 1, args)) {
+    (Value::Int(a), Value::Int(b)) if !floats => Ok(Some(a.cmp(b))),
+    (Value::Float(a), Value::Float(b)) if floats => Ok(a.partial_cmp(b)),
+    (a, b) => match (policy, a, b) {
+      (ComparisonPolicy::Numeric, Value::Int(a), Value::Int(b)) => Ok(Some(a.cmp(b))),
+      (ComparisonPolicy::Numeric, Value::Float(a), Value::Float(b)) => Ok(a.partial_cmp(b)),
+      (ComparisonPolicy::Numeric, Value::Int(a), Value::Float(b)) => Ok(cmp_int_float(*a, *b)),
+      (ComparisonPolicy::Numeric, Value::Float(a), Value::Int(b)) => {
+        Ok(cmp_int_float(*b, *a).map(Ordering::reverse))
+      }
+      _ => Err(InterpError::OperandTypeMismatch(
+        op,
+        a.type_name(),
+        b.type_name(),
+      )),
+    },
+  }
+}
+
+// Compares an int with a float by their exact values. Converting the int to a float instead would round ints above 2^53 and could make unequal values compare as equal
+fn cmp_int_float(i: i64, f: f64) -> Option<Ordering> {
+  if f.is_nan() {
+    None
+  } else if f >= 9_223_372_036_854_775_808.0 {
+    // 2^63, which is larger than any int
+    Some(Ordering::Less)
+  } else if f < -9_223_372_036_854_775_808.0 {
+    Some(Ordering::Greater)
+  } else {
+    // The whole part of the float is now in range, and only its fraction can break a tie
+    let whole = f.trunc();
+    Some(
+      i.cmp(&(whole as i64))
+        .then_with(|| 0.0.partial_cmp(&(f - whole)).unwrap()),
+    )
+  }
+}
+
 // A getter function for when you know what constructor of the Value enum you have and
 // you just want the underlying value(like a f64).
 #[inline(always)]
-fn get_arg<'a, T>(vars: &'a Environment, index: usize, args: &[usize]) -> T
+pub(crate) fn get_arg<'a, T>(vars: &'a Environment, index: usize, args: &[usize]) -> T
 where
   T: From<&'a Value>,
 {
   T::from(vars.get(&args[index]))
 }
 
+/// A value that a variable or a cell of the heap holds
 #[derive(Debug, Clone)]
-enum Value {
+pub enum Value {
+  /// An `int`
   Int(i64),
+  /// A `bool`
   Bool(bool),
+  /// A `float`
   Float(f64),
+  /// A `ptr` of any type
   Pointer(Pointer),
+  /// What a variable or cell holds before it is first written
   Uninitialized,
 }
 
@@ -188,10 +497,13 @@ impl Default for Value {
   }
 }
 
-#[derive(Debug, Clone, PartialEq)]
-struct Pointer {
-  base: usize,
-  offset: i64,
+/// A pointer into the allocation with the base ```base```, at ```offset``` cells from its start
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pointer {
+  /// The base of the allocation, which is never reused
+  pub base: usize,
+  /// Which cell it points to, which may be outside of the allocation
+  pub offset: i64,
 }
 
 impl Pointer {
@@ -203,6 +515,19 @@ impl Pointer {
   }
 }
 
+impl Value {
+  // The name of the Bril type of the value. Pointers are not distinguished by what they point to
+  pub(crate) const fn type_name(&self) -> &'static str {
+    match self {
+      Self::Int(_) => "int",
+      Self::Bool(_) => "bool",
+      Self::Float(_) => "float",
+      Self::Pointer(_) => "ptr",
+      Self::Uninitialized => "uninitialized",
+    }
+  }
+}
+
 impl fmt::Display for Value {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
@@ -215,6 +540,20 @@ impl fmt::Display for Value {
   }
 }
 
+// Whether `s`, the printed form of `v`, parses back into `v` the same way that arguments to "main" are parsed
+fn roundtrips(v: &Value, s: &str) -> bool {
+  match v {
+    Value::Int(i) => s.parse::<i64>().is_ok_and(|p| p == *i),
+    Value::Bool(b) => s.parse::<bool>().is_ok_and(|p| p == *b),
+    // Compare the bits so that 0 and -0 are told apart, but treat all NaNs as equal
+    Value::Float(f) => s
+      .parse::<f64>()
+      .is_ok_and(|p| p.to_bits() == f.to_bits() || (p.is_nan() && f.is_nan())),
+    // Pointers can not be parsed
+    Value::Pointer(_) | Value::Uninitialized => true,
+  }
+}
+
 impl From<&bril_rs::Literal> for Value {
   #[inline(always)]
   fn from(l: &bril_rs::Literal) -> Self {
@@ -281,75 +620,303 @@ impl<'a> From<&'a Value> for &'a Pointer {
   }
 }
 
+/// One step of Kahan-Babuska (Neumaier) compensated summation, which is what `fadd_kahan` does
+///
+/// Adds ```addend``` to the running ```sum``` and returns the new sum along with the new compensation, which holds the low order bits that were lost. Start with a sum and compensation of `0` and thread both through every step. The compensated total is `sum + compensation`, added once at the end. In a Bril program, `sum: float = fadd_kahan sum c x;` updates the variable `c` in place so the last step is `total: float = fadd sum c;`.
+///
+/// ```
+/// use brilirs::interp::kahan_add;
+///
+/// let (mut sum, mut compensation) = (0.0, 0.0);
+/// let mut naive = 0.0;
+/// for _ in 0..1000 {
+///   (sum, compensation) = kahan_add(sum, compensation, 0.1);
+///   naive += 0.1;
+/// }
+/// assert_eq!(sum + compensation, 100.0);
+/// assert_ne!(naive, 100.0);
+/// ```
+#[must_use]
+pub fn kahan_add(sum: f64, compensation: f64, addend: f64) -> (f64, f64) {
+  let total = sum + addend;
+  let lost = if sum.abs() >= addend.abs() {
+    (sum - total) + addend
+  } else {
+    (addend - total) + sum
+  };
+  (total, compensation + lost)
+}
+
+// The iteration order of a FxHashMap depends on its history, i.e. on how many elements it held before. Every diagnostic which prints the contents of a map goes through this so that two runs of the same program always produce the same bytes
+pub(crate) fn sorted_entries<K: Ord, V>(map: &FxHashMap<K, V>) -> Vec<(&K, &V)> {
+  let mut entries: Vec<_> = map.iter().collect();
+  entries.sort_unstable_by_key(|(k, _)| *k);
+  entries
+}
+
+// The value produced by `undef`. No allocation will ever have the base of the lenient pointer
+const fn undef_value(op_type: &bril_rs::Type, strict_undef: bool) -> Value {
+  if strict_undef {
+    return Value::Uninitialized;
+  }
+  match op_type {
+    bril_rs::Type::Int => Value::Int(0),
+    bril_rs::Type::Bool => Value::Bool(false),
+    bril_rs::Type::Float => Value::Float(0.0),
+    bril_rs::Type::Pointer(_) => Value::Pointer(Pointer {
+      base: usize::MAX,
+      offset: 0,
+    }),
+  }
+}
+
+// Picks the result of an integer operation according to the arithmetic mode. `checked` is `None` when the operation overflowed
+#[inline(always)]
+const fn int_arith(
+  mode: ArithmeticMode,
+  op: bril_rs::ValueOps,
+  arg0: i64,
+  arg1: i64,
+  checked: Option<i64>,
+  wrapped: i64,
+) -> Result<i64, InterpError> {
+  match (mode, checked) {
+    (ArithmeticMode::Wrapping, _) => Ok(wrapped),
+    (ArithmeticMode::Checked, Some(res)) => Ok(res),
+    (ArithmeticMode::Checked, None) => Err(InterpError::IntegerOverflow(op, arg0, arg1)),
+  }
+}
+
+// Rust can not change the rounding mode of the FPU so instead the round to nearest result `nearest` is moved to its neighbour when that is where the rounding mode would have put it.
+// `error` computes a value whose sign is the sign of the exact result minus `nearest`. Results that overflowed to infinity are not rounded back down
+#[inline(always)]
+fn round_float(mode: RoundingMode, nearest: f64, error: impl FnOnce(f64) -> f64) -> f64 {
+  if mode == RoundingMode::Nearest || !nearest.is_finite() {
+    return nearest;
+  }
+  let error = error(nearest);
+  match mode {
+    RoundingMode::TowardPositive if error > 0.0 => nearest.next_up(),
+    RoundingMode::TowardNegative if error < 0.0 => nearest.next_down(),
+    RoundingMode::TowardZero if nearest > 0.0 && error < 0.0 => nearest.next_down(),
+    RoundingMode::TowardZero if nearest < 0.0 && error > 0.0 => nearest.next_up(),
+    _ => nearest,
+  }
+}
+
+// The exact rounding error of `sum = a + b` using Knuth's TwoSum
+#[inline(always)]
+fn add_error(a: f64, b: f64, sum: f64) -> f64 {
+  let b_virtual = sum - a;
+  let a_virtual = sum - b_virtual;
+  (a - a_virtual) + (b - b_virtual)
+}
+
+// `fadd`, `fsub`, `fmul` and `fdiv` rounded with `mode`
+#[inline(always)]
+pub(crate) fn fadd(mode: RoundingMode, a: f64, b: f64) -> f64 {
+  round_float(mode, a + b, |res| add_error(a, b, res))
+}
+
+#[inline(always)]
+pub(crate) fn fsub(mode: RoundingMode, a: f64, b: f64) -> f64 {
+  round_float(mode, a - b, |res| add_error(a, -b, res))
+}
+
+#[inline(always)]
+pub(crate) fn fmul(mode: RoundingMode, a: f64, b: f64) -> f64 {
+  round_float(mode, a * b, |res| a.mul_add(b, -res))
+}
+
+#[inline(always)]
+pub(crate) fn fdiv(mode: RoundingMode, a: f64, b: f64) -> f64 {
+  round_float(mode, a / b, |res| {
+    // The remainder a - res * b is exact, and the quotient is off in the direction of remainder / b
+    -res.mul_add(b, -a) * b.signum()
+  })
+}
+
+// `div` rounded with `mode`. Like `wrapping_div`, this panics when `b` is zero
+#[inline(always)]
+pub(crate) const fn divide(mode: DivisionMode, a: i64, b: i64) -> i64 {
+  match mode {
+    DivisionMode::Truncating => a.wrapping_div(b),
+    DivisionMode::Flooring => floor_div(a, b),
+  }
+}
+
+// Division that rounds toward negative infinity. The remainder is 0 when the quotient wraps, which is only `i64::MIN / -1`
+const fn floor_div(a: i64, b: i64) -> i64 {
+  let q = a.wrapping_div(b);
+  if a.wrapping_rem(b) != 0 && (a < 0) != (b < 0) {
+    q - 1
+  } else {
+    q
+  }
+}
+
+// Stein's binary gcd algorithm. gcd(0, 0) is defined to be 0
+const fn binary_gcd(mut a: u64, mut b: u64) -> u64 {
+  if a == 0 || b == 0 {
+    return a | b;
+  }
+  let shift = (a | b).trailing_zeros();
+  a >>= a.trailing_zeros();
+  loop {
+    b >>= b.trailing_zeros();
+    if a > b {
+      std::mem::swap(&mut a, &mut b);
+    }
+    b -= a;
+    if b == 0 {
+      return a << shift;
+    }
+  }
+}
+
 // Sets up the Environment for the next function call with the supplied arguments
-fn make_func_args<'a>(callee_func: &'a BBFunction, args: &[usize], vars: &mut Environment) {
+fn make_func_args<'a>(
+  callee_func: &'a BBFunction,
+  args: &[usize],
+  vars: &mut Environment,
+  relaxed_calls: bool,
+) {
   vars.push_frame(callee_func.num_of_vars);
 
   args
     .iter()
     .zip(callee_func.args_as_nums.iter())
-    .for_each(|(arg_name, expected_arg)| {
-      let arg = vars.get_from_last_frame(arg_name).clone();
+    .enumerate()
+    .for_each(|(i, (arg_name, expected_arg))| {
+      let arg = match vars.get_from_last_frame(arg_name) {
+        // The only coercion allowed by relaxed calls. Going from float to int would need a choice of rounding
+        Value::Int(n) if relaxed_calls && callee_func.args[i].arg_type == bril_rs::Type::Float => {
+          Value::Float(*n as f64)
+        }
+        arg => arg.clone(),
+      };
       vars.set(*expected_arg, arg);
     })
 }
 
 #[inline(always)]
-fn execute_value_op<'a, T: std::io::Write>(
-  state: &'a mut State<T>,
+fn execute_value_op<
+  'a,
+  const INSTRUMENTED: bool,
+  T: std::io::Write,
+  U: std::io::Write,
+  H: HeapBackend,
+>(
+  state: &'a mut State<T, U, H>,
   op: &bril_rs::ValueOps,
-  dest: usize,
-  args: &[usize],
+  op_type: &bril_rs::Type,
+  numified_code: &NumifiedInstruction,
   labels: &[String],
-  funcs: &[usize],
   last_label: Option<&String>,
+  pos: Option<Position>,
 ) -> Result<(), InterpError> {
+  let dest = numified_code.dest.unwrap();
+  let args = &numified_code.args;
+  let funcs = &numified_code.funcs;
+  if INSTRUMENTED {
+    if let Some(semantics) = state.semantics.as_deref_mut() {
+      if let Some(res) = crate::semantics::apply(semantics, *op, &state.env, args) {
+        state.env.set(dest, res);
+        return Ok(());
+      }
+    }
+  }
+
   use bril_rs::ValueOps::*;
   match *op {
     Add => {
       let arg0 = get_arg::<i64>(&state.env, 0, args);
       let arg1 = get_arg::<i64>(&state.env, 1, args);
-      state.env.set(dest, Value::Int(arg0.wrapping_add(arg1)));
+      let checked = arg0.checked_add(arg1);
+      let res = int_arith(
+        state.arithmetic_for::<INSTRUMENTED>(*op),
+        *op,
+        arg0,
+        arg1,
+        checked,
+        arg0.wrapping_add(arg1),
+      )?;
+      if INSTRUMENTED && state.options.wraparound_warnings && checked.is_none() {
+        state.warn_wraparound(*op, arg0, arg1, res, pos)?;
+      }
+      state.env.set(dest, Value::Int(res));
     }
     Mul => {
       let arg0 = get_arg::<i64>(&state.env, 0, args);
       let arg1 = get_arg::<i64>(&state.env, 1, args);
-      state.env.set(dest, Value::Int(arg0.wrapping_mul(arg1)));
+      let checked = arg0.checked_mul(arg1);
+      let res = int_arith(
+        state.arithmetic_for::<INSTRUMENTED>(*op),
+        *op,
+        arg0,
+        arg1,
+        checked,
+        arg0.wrapping_mul(arg1),
+      )?;
+      if INSTRUMENTED && state.options.wraparound_warnings && checked.is_none() {
+        state.warn_wraparound(*op, arg0, arg1, res, pos)?;
+      }
+      state.env.set(dest, Value::Int(res));
     }
     Sub => {
       let arg0 = get_arg::<i64>(&state.env, 0, args);
       let arg1 = get_arg::<i64>(&state.env, 1, args);
-      state.env.set(dest, Value::Int(arg0.wrapping_sub(arg1)));
+      let checked = arg0.checked_sub(arg1);
+      let res = int_arith(
+        state.arithmetic_for::<INSTRUMENTED>(*op),
+        *op,
+        arg0,
+        arg1,
+        checked,
+        arg0.wrapping_sub(arg1),
+      )?;
+      if INSTRUMENTED && state.options.wraparound_warnings && checked.is_none() {
+        state.warn_wraparound(*op, arg0, arg1, res, pos)?;
+      }
+      state.env.set(dest, Value::Int(res));
     }
     Div => {
       let arg0 = get_arg::<i64>(&state.env, 0, args);
       let arg1 = get_arg::<i64>(&state.env, 1, args);
-      state.env.set(dest, Value::Int(arg0.wrapping_div(arg1)));
+      let res = divide(state.options.division, arg0, arg1);
+      state.env.set(dest, Value::Int(res));
     }
-    Eq => {
-      let arg0 = get_arg::<i64>(&state.env, 0, args);
-      let arg1 = get_arg::<i64>(&state.env, 1, args);
-      state.env.set(dest, Value::Bool(arg0 == arg1));
+    Eq | Feq => {
+      let ord = compare_args(state.options.comparison, *op, &state.env, args)?;
+      state
+        .env
+        .set(dest, Value::Bool(ord == Some(Ordering::Equal)));
     }
-    Lt => {
-      let arg0 = get_arg::<i64>(&state.env, 0, args);
-      let arg1 = get_arg::<i64>(&state.env, 1, args);
-      state.env.set(dest, Value::Bool(arg0 < arg1));
+    Lt | Flt => {
+      let ord = compare_args(state.options.comparison, *op, &state.env, args)?;
+      state
+        .env
+        .set(dest, Value::Bool(ord == Some(Ordering::Less)));
     }
-    Gt => {
-      let arg0 = get_arg::<i64>(&state.env, 0, args);
-      let arg1 = get_arg::<i64>(&state.env, 1, args);
-      state.env.set(dest, Value::Bool(arg0 > arg1));
+    Gt | Fgt => {
+      let ord = compare_args(state.options.comparison, *op, &state.env, args)?;
+      state
+        .env
+        .set(dest, Value::Bool(ord == Some(Ordering::Greater)));
     }
-    Le => {
-      let arg0 = get_arg::<i64>(&state.env, 0, args);
-      let arg1 = get_arg::<i64>(&state.env, 1, args);
-      state.env.set(dest, Value::Bool(arg0 <= arg1));
+    Le | Fle => {
+      let ord = compare_args(state.options.comparison, *op, &state.env, args)?;
+      state.env.set(
+        dest,
+        Value::Bool(matches!(ord, Some(Ordering::Less | Ordering::Equal))),
+      );
     }
-    Ge => {
-      let arg0 = get_arg::<i64>(&state.env, 0, args);
-      let arg1 = get_arg::<i64>(&state.env, 1, args);
-      state.env.set(dest, Value::Bool(arg0 >= arg1));
+    Ge | Fge => {
+      let ord = compare_args(state.options.comparison, *op, &state.env, args)?;
+      state.env.set(
+        dest,
+        Value::Bool(matches!(ord, Some(Ordering::Greater | Ordering::Equal))),
+      );
     }
     Not => {
       let arg0 = get_arg::<bool>(&state.env, 0, args);
@@ -366,60 +933,79 @@ fn execute_value_op<'a, T: std::io::Write>(
       state.env.set(dest, Value::Bool(arg0 || arg1));
     }
     Id => {
+      if state.options.profiling && state.options.self_ids && dest == args[0] {
+        state.self_ids += 1;
+      }
       let src = get_value(&state.env, 0, args).clone();
       state.env.set(dest, src);
     }
     Fadd => {
       let arg0 = get_arg::<f64>(&state.env, 0, args);
       let arg1 = get_arg::<f64>(&state.env, 1, args);
-      state.env.set(dest, Value::Float(arg0 + arg1));
+      let res = fadd(state.options.rounding, arg0, arg1);
+      state.env.set(dest, Value::Float(res));
     }
     Fmul => {
       let arg0 = get_arg::<f64>(&state.env, 0, args);
       let arg1 = get_arg::<f64>(&state.env, 1, args);
-      state.env.set(dest, Value::Float(arg0 * arg1));
+      let res = fmul(state.options.rounding, arg0, arg1);
+      state.env.set(dest, Value::Float(res));
     }
     Fsub => {
       let arg0 = get_arg::<f64>(&state.env, 0, args);
       let arg1 = get_arg::<f64>(&state.env, 1, args);
-      state.env.set(dest, Value::Float(arg0 - arg1));
+      let res = fsub(state.options.rounding, arg0, arg1);
+      state.env.set(dest, Value::Float(res));
     }
     Fdiv => {
       let arg0 = get_arg::<f64>(&state.env, 0, args);
       let arg1 = get_arg::<f64>(&state.env, 1, args);
-      state.env.set(dest, Value::Float(arg0 / arg1));
-    }
-    Feq => {
-      let arg0 = get_arg::<f64>(&state.env, 0, args);
-      let arg1 = get_arg::<f64>(&state.env, 1, args);
-      state.env.set(dest, Value::Bool(arg0 == arg1));
-    }
-    Flt => {
-      let arg0 = get_arg::<f64>(&state.env, 0, args);
-      let arg1 = get_arg::<f64>(&state.env, 1, args);
-      state.env.set(dest, Value::Bool(arg0 < arg1));
-    }
-    Fgt => {
-      let arg0 = get_arg::<f64>(&state.env, 0, args);
-      let arg1 = get_arg::<f64>(&state.env, 1, args);
-      state.env.set(dest, Value::Bool(arg0 > arg1));
-    }
-    Fle => {
-      let arg0 = get_arg::<f64>(&state.env, 0, args);
-      let arg1 = get_arg::<f64>(&state.env, 1, args);
-      state.env.set(dest, Value::Bool(arg0 <= arg1));
-    }
-    Fge => {
-      let arg0 = get_arg::<f64>(&state.env, 0, args);
-      let arg1 = get_arg::<f64>(&state.env, 1, args);
-      state.env.set(dest, Value::Bool(arg0 >= arg1));
+      let res = fdiv(state.options.rounding, arg0, arg1);
+      state.env.set(dest, Value::Float(res));
     }
     Call => {
-      let callee_func = state.prog.get(funcs[0]).unwrap();
+      let Some(callee_func) = state.prog.get(funcs[0]) else {
+        let result = state.native_call(funcs[0], args, Some(op_type))?.unwrap();
+        state.env.set(dest, result);
+        return Ok(());
+      };
+
+      if let Some(stubs) = state.stubs {
+        let result = state.stub_call(stubs, callee_func, args)?.unwrap();
+        state.env.set(dest, result);
+        return Ok(());
+      }
+
+      let pure_call = INSTRUMENTED && state.enter_pure_call(funcs[0], args)?;
+
+      make_func_args(
+        callee_func,
+        args,
+        &mut state.env,
+        state.options.relaxed_calls,
+      );
+
+      // Reserve a spot for this call before executing it so that the returns are kept in call order
+      let slot = state.options.capture_returns.then(|| {
+        state.call_returns.push((callee_func.name.clone(), None));
+        state.call_returns.len() - 1
+      });
 
-      make_func_args(callee_func, args, &mut state.env);
+      let result = execute::<INSTRUMENTED, _, _, _>(state, callee_func)?.unwrap();
+      if pure_call {
+        state.leave_pure_call();
+      }
 
-      let result = execute(state, callee_func)?.unwrap();
+      if let Some(slot) = slot {
+        state.call_returns[slot].1 = Some(result.clone());
+      }
+      if state.options.dangling_returns {
+        if let Value::Pointer(p) = &result {
+          state
+            .returned_bases
+            .insert(p.base, callee_func.name.clone());
+        }
+      }
 
       state.env.pop_frame();
 
@@ -439,106 +1025,479 @@ fn execute_value_op<'a, T: std::io::Write>(
     },
     Alloc => {
       let arg0 = get_arg::<i64>(&state.env, 0, args);
-      let res = state.heap.alloc(arg0)?;
+      let res = state.heap.alloc(arg0, state.options.max_alloc_size)?;
       state.env.set(dest, res)
     }
     Load => {
       let arg0 = get_arg::<&Pointer>(&state.env, 0, args);
-      let res = state.heap.read(arg0)?;
+      let res = match state.heap.read(arg0) {
+        Err(InterpError::UsingUninitializedMemory) if state.options.dump_uninit_reads => {
+          state.heap.dump_block(arg0, &mut state.profiling_out)?;
+          return Err(InterpError::UsingUninitializedMemory);
+        }
+        Err(InterpError::UsingUninitializedMemory) if state.options.strict_uninit => {
+          return Err(InterpError::UsingUninitializedMemoryAt(
+            arg0.base,
+            arg0.offset,
+          ));
+        }
+        res => res?,
+      };
       state.env.set(dest, res.clone())
     }
     PtrAdd => {
       let arg0 = get_arg::<&Pointer>(&state.env, 0, args);
       let arg1 = get_arg::<i64>(&state.env, 1, args);
-      let res = Value::Pointer(arg0.add(arg1));
-      state.env.set(dest, res)
+      let res = arg0.add(arg1);
+      if state.options.strict_pointer_offsets && res.offset < 0 {
+        return Err(InterpError::NegativePointerOffset(res.offset));
+      }
+      if state.options.strict_pointer_bounds {
+        // A pointer into an allocation that has been freed is reported when it is used
+        if let Some(len) = state.heap.block(res.base).map(<[Value]>::len) {
+          if res.offset > len as i64 {
+            return Err(InterpError::PointerPastEnd(res.base, res.offset, len));
+          }
+        }
+      }
+      state.env.set(dest, Value::Pointer(res))
     }
+    _ => execute_extension_value_op(state, op, dest, args, labels)?,
   }
   Ok(())
 }
 
-#[inline(always)]
-fn execute_effect_op<'a, T: std::io::Write>(
-  state: &'a mut State<T>,
-  func: &BBFunction,
-  op: &bril_rs::EffectOps,
+// The value operations of the extensions that are not a part of Bril itself. They are kept out of `execute` so that they do not slow down the common operations
+#[inline(never)]
+fn execute_extension_value_op<T: std::io::Write, U: std::io::Write, H: HeapBackend>(
+  state: &mut State<T, U, H>,
+  op: &bril_rs::ValueOps,
+  dest: usize,
   args: &[usize],
-  funcs: &[usize],
-  curr_block: &BasicBlock,
-  next_block_idx: &mut Option<usize>,
-) -> Result<Option<Value>, InterpError> {
-  use bril_rs::EffectOps::*;
-  match op {
-    Jump => {
-      *next_block_idx = Some(curr_block.exit[0]);
+  labels: &[String],
+) -> Result<(), InterpError> {
+  use bril_rs::ValueOps::*;
+  match *op {
+    Gcd => {
+      let arg0 = get_arg::<i64>(&state.env, 0, args);
+      let arg1 = get_arg::<i64>(&state.env, 1, args);
+      // The only result that does not fit is 2^63, i.e. the magnitude of i64::MIN
+      let gcd = binary_gcd(arg0.unsigned_abs(), arg1.unsigned_abs());
+      let res = int_arith(
+        state.options.arithmetic_for(*op),
+        *op,
+        arg0,
+        arg1,
+        i64::try_from(gcd).ok(),
+        gcd as i64,
+      )?;
+      state.env.set(dest, Value::Int(res));
     }
-    Branch => {
-      let bool_arg0 = get_arg::<bool>(&state.env, 0, args);
-      let exit_idx = if bool_arg0 { 0 } else { 1 };
-      *next_block_idx = Some(curr_block.exit[exit_idx]);
+    Lcm => {
+      let arg0 = get_arg::<i64>(&state.env, 0, args);
+      let arg1 = get_arg::<i64>(&state.env, 1, args);
+      let (a, b) = (arg0.unsigned_abs(), arg1.unsigned_abs());
+      // Computed in u128 so that the product of the two magnitudes can not overflow before the check
+      let lcm = if a == 0 || b == 0 {
+        0
+      } else {
+        u128::from(a / binary_gcd(a, b)) * u128::from(b)
+      };
+      let res = int_arith(
+        state.options.arithmetic_for(*op),
+        *op,
+        arg0,
+        arg1,
+        i64::try_from(lcm).ok(),
+        lcm as i64,
+      )?;
+      state.env.set(dest, Value::Int(res));
     }
-    Return => {
-      return Ok(
-        func
-          .return_type
-          .as_ref()
-          .map(|_| get_value(&state.env, 0, args).clone()),
-      )
+    Rotl => {
+      let arg0 = get_arg::<i64>(&state.env, 0, args);
+      let arg1 = get_arg::<i64>(&state.env, 1, args);
+      // rem_euclid so that a negative amount rotates the other way
+      let res = arg0.rotate_left(arg1.rem_euclid(64) as u32);
+      state.env.set(dest, Value::Int(res));
     }
-    Print => {
-      writeln!(
-        state.out,
-        "{}",
-        args
-          .iter()
-          .map(|a| state.env.get(a).to_string())
-          .collect::<Vec<String>>()
-          .join(" ")
-      )
-      // We call flush here in case `out` is a https://doc.rust-lang.org/std/io/struct.BufWriter.html
-      // Otherwise we would expect this flush to be a nop.
-      .and_then(|_| state.out.flush())
-      .map_err(|e| InterpError::IoError(Box::new(e)))?;
+    Rotr => {
+      let arg0 = get_arg::<i64>(&state.env, 0, args);
+      let arg1 = get_arg::<i64>(&state.env, 1, args);
+      let res = arg0.rotate_right(arg1.rem_euclid(64) as u32);
+      state.env.set(dest, Value::Int(res));
     }
-    Nop => {}
-    Call => {
-      let callee_func = state.prog.get(funcs[0]).unwrap();
-
-      make_func_args(callee_func, args, &mut state.env);
-
-      execute(state, callee_func)?;
-      state.env.pop_frame();
+    Dump => {
+      let src = get_value(&state.env, 0, args).clone();
+      writeln!(state.profiling_out, "{}: {src}", labels[0])
+        .and_then(|_| state.profiling_out.flush())
+        .map_err(|e| InterpError::IoError(Box::new(e)))?;
+      state.env.set(dest, src);
     }
-    Store => {
+    Fapproxeq | Fapprox => {
+      let arg0 = get_arg::<f64>(&state.env, 0, args);
+      let arg1 = get_arg::<f64>(&state.env, 1, args);
+      let tolerance = get_arg::<f64>(&state.env, 2, args);
+      // The tolerance is absolute rather than relative to the magnitude of the arguments. Any NaN makes the comparison false while equal infinities are approximately equal
+      let res = !tolerance.is_nan() && (arg0 == arg1 || (arg0 - arg1).abs() <= tolerance);
+      state.env.set(dest, Value::Bool(res));
+    }
+    FaddKahan => {
+      let sum = get_arg::<f64>(&state.env, 0, args);
+      let compensation = get_arg::<f64>(&state.env, 1, args);
+      let addend = get_arg::<f64>(&state.env, 2, args);
+      let (sum, compensation) = kahan_add(sum, compensation, addend);
+      // The compensation is threaded through the second argument
+      state.env.set(args[1], Value::Float(compensation));
+      state.env.set(dest, Value::Float(sum));
+    }
+    Alloca => {
+      let arg0 = get_arg::<i64>(&state.env, 0, args);
+      let res = state.heap.alloc(arg0, state.options.max_alloc_size)?;
+      let base = <&Pointer>::from(&res).base;
+      state
+        .frame_allocs
+        .push((state.env.stack_pointers.len(), base));
+      state.env.set(dest, res)
+    }
+    Dup => {
+      let arg0 = get_arg::<&Pointer>(&state.env, 0, args);
+      let arg1 = get_arg::<i64>(&state.env, 1, args);
+      let res = state.heap.dup(arg0, arg1, state.options.max_alloc_size)?;
+      state.env.set(dest, res)
+    }
+    Memdup => {
+      let arg0 = get_arg::<&Pointer>(&state.env, 0, args);
+      let size = match state.heap.block(arg0.base) {
+        Some(block) if arg0.offset == 0 => block.len() as i64,
+        _ => return Err(InterpError::IllegalMemdup(arg0.base, arg0.offset)),
+      };
+      let res = state.heap.dup(arg0, size, state.options.max_alloc_size)?;
+      state.env.set(dest, res)
+    }
+    Samebase => {
+      let arg0 = get_arg::<&Pointer>(&state.env, 0, args);
+      let arg1 = get_arg::<&Pointer>(&state.env, 1, args);
+      state.env.set(dest, Value::Bool(arg0.base == arg1.base));
+    }
+    Ptrdiff => {
+      let arg0 = get_arg::<&Pointer>(&state.env, 0, args);
+      let arg1 = get_arg::<&Pointer>(&state.env, 1, args);
+      if arg0.base != arg1.base {
+        return Err(InterpError::IncomparablePointers(arg0.base, arg1.base));
+      }
+      state
+        .env
+        .set(dest, Value::Int(arg0.offset.wrapping_sub(arg1.offset)));
+    }
+    Heapblocks => {
+      let live = state.heap.len() as i64;
+      state.env.set(dest, Value::Int(live));
+    }
+    Bool2int => {
+      let arg0 = get_arg::<bool>(&state.env, 0, args);
+      state.env.set(dest, Value::Int(i64::from(arg0)));
+    }
+    Parity => {
+      let arg0 = get_arg::<i64>(&state.env, 0, args);
+      state
+        .env
+        .set(dest, Value::Int(i64::from(arg0.count_ones() % 2)));
+    }
+    Int2bool => {
+      let arg0 = get_arg::<i64>(&state.env, 0, args);
+      state.env.set(dest, Value::Bool(arg0 != 0));
+    }
+    Clock => {
+      // A logical clock rather than the time so that programs which use it are deterministic. Instructions are counted a whole block at a time when it is entered, so every `clock` in a block reads the same value, which already includes the rest of the block
+      state
+        .env
+        .set(dest, Value::Int(i64::from(state.instruction_count)));
+    }
+    UniqueId => {
+      // Unlike `clock`, this is counted per execution of the instruction so two of them never read the same value
+      state.env.set(dest, Value::Int(state.unique_ids));
+      state.unique_ids += 1;
+    }
+    // These need the type of the destination or the function being executed so they are handled by execute_from
+    Undef | Argc => unreachable!(),
+    _ => unreachable!(),
+  }
+  Ok(())
+}
+
+#[inline(always)]
+fn execute_effect_op<
+  'a,
+  const INSTRUMENTED: bool,
+  T: std::io::Write,
+  U: std::io::Write,
+  H: HeapBackend,
+>(
+  state: &'a mut State<T, U, H>,
+  func: &BBFunction,
+  op: &bril_rs::EffectOps,
+  numified_code: &NumifiedInstruction,
+  curr_block: &BasicBlock,
+  next_block_idx: &mut Option<usize>,
+  pos: Option<Position>,
+) -> Result<Option<Value>, InterpError> {
+  use bril_rs::EffectOps::*;
+  let args = &numified_code.args;
+  let funcs = &numified_code.funcs;
+  match op {
+    Jump => {
+      *next_block_idx = Some(curr_block.exit[0]);
+    }
+    Branch => {
+      let bool_arg0 = get_arg::<bool>(&state.env, 0, args);
+      let exit_idx = if bool_arg0 { 0 } else { 1 };
+      *next_block_idx = Some(curr_block.exit[exit_idx]);
+    }
+    Return => {
+      return Ok(
+        func
+          .return_type
+          .as_ref()
+          .map(|_| get_value(&state.env, 0, args).clone()),
+      )
+    }
+    Print => {
+      let printed = args
+        .iter()
+        .map(|a| match state.env.get(a) {
+          Value::Bool(b) if state.options.numeric_bools => u8::from(*b).to_string(),
+          v => v.to_string(),
+        })
+        .collect::<Vec<String>>();
+      if state.options.roundtrip_check {
+        for (a, s) in args.iter().zip(printed.iter()) {
+          let v = state.env.get(a);
+          if !roundtrips(v, s) {
+            writeln!(
+              state.profiling_out,
+              "warning: printed `{s}` which does not parse back into {v:?}"
+            )
+            .map_err(|e| InterpError::IoError(Box::new(e)))?;
+          }
+        }
+      }
+      if state.options.print_provenance {
+        state.write_provenance(func, args, pos)?;
+      }
+      let mut line = printed.join(" ");
+      line.push('\n');
+      if state.options.print_inst_count {
+        line = format!("[inst {}] {line}", state.instruction_count);
+      }
+      if let Some(on_print) = state.on_print.as_mut() {
+        line = on_print(&line);
+      }
+      state.throttled_print(line)?;
+    }
+    Nop => {}
+    // There is only ever one thread so there is nothing to order. It is still counted as an instruction
+    Fence => {}
+    Debugbreak => {
+      // Without a debugger attached this is the same as a nop
+      if let Some(on_break) = state.on_break.as_mut() {
+        if on_break(&breakpoint(&state.env, &state.frames, func, pos)) == Resume::Abort {
+          return Err(InterpError::AbortedAtBreakpoint);
+        }
+      }
+    }
+    Call => match (state.prog.get(funcs[0]), state.stubs) {
+      (None, _) => {
+        state.native_call(funcs[0], args, None)?;
+      }
+      (Some(callee_func), Some(stubs)) => {
+        state.stub_call(stubs, callee_func, args)?;
+      }
+      (Some(callee_func), None) => {
+        let pure_call = INSTRUMENTED && state.enter_pure_call(funcs[0], args)?;
+        make_func_args(
+          callee_func,
+          args,
+          &mut state.env,
+          state.options.relaxed_calls,
+        );
+
+        execute::<INSTRUMENTED, _, _, _>(state, callee_func)?;
+        if pure_call {
+          state.leave_pure_call();
+        }
+        state.env.pop_frame();
+      }
+    },
+    Store => {
       let arg0 = get_arg::<&Pointer>(&state.env, 0, args);
       let arg1 = get_value(&state.env, 1, args);
+      if state.options.check_escapes {
+        if let Value::Pointer(p) = arg1 {
+          state.check_escape(arg0.base, p.base)?;
+        }
+      }
       state.heap.write(arg0, arg1.clone())?
     }
     Free => {
+      if state.options.dangling_returns {
+        state.check_dangling_return(func, args[0], pos)?;
+      }
       let arg0 = get_arg::<&Pointer>(&state.env, 0, args);
       state.heap.free(arg0)?
     }
-    Speculate | Commit | Guard => unimplemented!(),
+    Speculate => {
+      let frame = state.env.current_pointer;
+      state.speculations.push(Speculation {
+        depth: state.env.stack_pointers.len(),
+        vars: state.env.env[frame..frame + state.env.current_frame_size].to_vec(),
+        output: Vec::new(),
+      });
+    }
+    Commit => {
+      if !state.is_speculating() {
+        return Err(InterpError::NotSpeculating("commit".to_string()));
+      }
+      let speculation = state.speculations.pop().unwrap();
+      match state.speculations.last_mut() {
+        Some(outer) => outer.output.extend(speculation.output),
+        None => {
+          let lines = speculation.output.iter().filter(|&&b| b == b'\n').count();
+          state.write_out(&speculation.output, lines as u32)?;
+        }
+      }
+    }
+    Guard => {
+      if !state.is_speculating() {
+        return Err(InterpError::NotSpeculating("guard".to_string()));
+      }
+      if get_arg::<bool>(&state.env, 0, args) {
+        *next_block_idx = curr_block.exit.get(1).copied();
+      } else {
+        // Abort by rolling back the variables and discarding the output. The heap is left as it is
+        let speculation = state.speculations.pop().unwrap();
+        let frame = state.env.current_pointer;
+        state.env.env[frame..frame + speculation.vars.len()].clone_from_slice(&speculation.vars);
+        *next_block_idx = Some(curr_block.exit[0]);
+      }
+    }
   }
   Ok(None)
 }
 
-fn execute<'a, T: std::io::Write>(
-  state: &mut State<'a, T>,
+// Runs `func` with the copy of the interpreter that only does the bookkeeping that `state` needs
+fn run<'a, T: std::io::Write, U: std::io::Write, H: HeapBackend>(
+  state: &mut State<'a, T, U, H>,
+  func: &'a BBFunction,
+) -> Result<Option<Value>, PositionalInterpError> {
+  if state.instrumented() {
+    execute::<true, _, _, _>(state, func)
+  } else {
+    execute::<false, _, _, _>(state, func)
+  }
+}
+
+// `INSTRUMENTED` is false for the copy that is run when nothing needs to look at each block, instruction, or call, so that it is as fast as it can be
+fn execute<'a, const INSTRUMENTED: bool, T: std::io::Write, U: std::io::Write, H: HeapBackend>(
+  state: &mut State<'a, T, U, H>,
+  func: &'a BBFunction,
+) -> Result<Option<Value>, PositionalInterpError> {
+  if INSTRUMENTED {
+    state.enter_function(func);
+    if state.options.taint {
+      state.taint.enter_function(&state.env, func);
+    }
+    if state.options.dead_stores {
+      state.dead_stores.enter_function(&state.env, func);
+    }
+  }
+  let mut result = execute_from::<INSTRUMENTED, _, _, _>(state, func, 0, 0, None);
+  if INSTRUMENTED {
+    if let Err(e) = &result {
+      state.dump_on_error(func, e);
+    }
+  }
+  if !state.frame_allocs.is_empty() {
+    let freed = free_frame_allocs(state, func);
+    result = result.and_then(|r| freed.map(|()| r));
+  }
+  if INSTRUMENTED {
+    state.leave_function(func);
+  }
+  result
+}
+
+// Frame allocations do not outlive the call that made them, so the ones made by the call of `func` that is returning are freed. They are kept while unwinding to take a checkpoint since they are part of it
+#[cold]
+fn free_frame_allocs<T: std::io::Write, U: std::io::Write, H: HeapBackend>(
+  state: &mut State<'_, T, U, H>,
+  func: &BBFunction,
+) -> Result<(), PositionalInterpError> {
+  if !state.checkpoint_frames.is_empty() {
+    return Ok(());
+  }
+  let mut result = Ok(());
+  let depth = state.env.stack_pointers.len();
+  while state.frame_allocs.last().is_some_and(|(d, _)| *d == depth) {
+    let (_, base) = state.frame_allocs.pop().unwrap();
+    // It may have already been freed with `free`
+    if state.heap.block(base).is_some() {
+      if let Err(e) = state.heap.free(&Pointer { base, offset: 0 }) {
+        if result.is_ok() {
+          result = Err(e.add_pos(func.pos));
+        }
+      }
+    }
+  }
+  result
+}
+
+// Executes `func` starting from instruction `start_instr` of the block `curr_block_idx`, where `current_label` is the label of the block that was executed before it.
+// This is the start of the function unless we are resuming from a checkpoint
+#[inline(always)]
+fn execute_from<
+  'a,
+  const INSTRUMENTED: bool,
+  T: std::io::Write,
+  U: std::io::Write,
+  H: HeapBackend,
+>(
+  state: &mut State<'a, T, U, H>,
   func: &'a BBFunction,
+  mut curr_block_idx: usize,
+  mut start_instr: usize,
+  mut current_label: Option<&'a String>,
 ) -> Result<Option<Value>, PositionalInterpError> {
   let mut last_label;
-  let mut current_label = None;
-  let mut curr_block_idx = 0;
   let mut result = None;
 
   loop {
     let curr_block = &func.blocks[curr_block_idx];
     let curr_instrs = &curr_block.instrs;
     let curr_numified_instrs = &curr_block.numified_instrs;
-    // WARNING!!! We can add the # of instructions at once because you can only jump to a new block at the end. This may need to be changed if speculation is implemented
-    state.instruction_count += curr_instrs.len() as u32;
     last_label = current_label;
+    // When resuming in the middle of a block, its instructions have already been counted
+    if start_instr == 0 {
+      if INSTRUMENTED
+        && state
+          .checkpoint_at
+          .is_some_and(|n| state.instruction_count >= n)
+      {
+        return Err(state.start_checkpoint(Frame {
+          func: func.name.clone(),
+          block: curr_block_idx,
+          instr: 0,
+          last_label: last_label.cloned(),
+          dest: None,
+        }));
+      }
+      // WARNING!!! We can add the # of instructions at once because you can only jump to a new block at the end. This is why `guard` also ends a block
+      state.instruction_count += curr_instrs.len() as u32;
+      if INSTRUMENTED {
+        state
+          .enter_block(func, curr_block_idx)
+          .map_err(PositionalInterpError::new)?;
+      }
+    }
     current_label = curr_block.label.as_ref();
 
     // This helps to implement fallthrough with basic blocks when there is no control flow instruction at the end of the block
@@ -548,7 +1507,19 @@ fn execute<'a, T: std::io::Write>(
       None
     };
 
-    for (code, numified_code) in curr_instrs.iter().zip(curr_numified_instrs.iter()) {
+    for (i, (code, numified_code)) in (start_instr..).zip(
+      curr_instrs[start_instr..]
+        .iter()
+        .zip(curr_numified_instrs[start_instr..].iter()),
+    ) {
+      let taint = if INSTRUMENTED {
+        state
+          .before_instruction(func, code, numified_code, last_label)
+          .map_err(|e| e.add_pos(code.get_pos()))?
+      } else {
+        None
+      };
+
       match code {
         Instruction::Constant {
           op: bril_rs::ConstOps::Const,
@@ -574,25 +1545,48 @@ fn execute<'a, T: std::io::Write>(
               .set(numified_code.dest.unwrap(), Value::from(value));
           };
         }
+        Instruction::Value {
+          op: bril_rs::ValueOps::Undef,
+          op_type,
+          ..
+        } => state.env.set(
+          numified_code.dest.unwrap(),
+          undef_value(
+            op_type,
+            state.options.strict_undef || state.options.strict_uninit,
+          ),
+        ),
+        Instruction::Value {
+          op: bril_rs::ValueOps::Argc,
+          ..
+        } => state.env.set(
+          numified_code.dest.unwrap(),
+          Value::Int(func.args.len() as i64),
+        ),
         Instruction::Value {
           op,
           dest: _,
-          op_type: _,
+          op_type,
           args: _,
           labels,
           funcs: _,
           pos,
         } => {
-          execute_value_op(
+          execute_value_op::<INSTRUMENTED, _, _, _>(
             state,
             op,
-            numified_code.dest.unwrap(),
-            &numified_code.args,
+            op_type,
+            numified_code,
             labels,
-            &numified_code.funcs,
             last_label,
+            *pos,
           )
-          .map_err(|e| e.add_pos(*pos))?;
+          .map_err(|e| {
+            if INSTRUMENTED {
+              state.record_frame(func, curr_block_idx, i, numified_code.dest, last_label);
+            }
+            e.add_pos(*pos)
+          })?;
         }
         Instruction::Effect {
           op,
@@ -601,32 +1595,132 @@ fn execute<'a, T: std::io::Write>(
           funcs: _,
           pos,
         } => {
-          result = execute_effect_op(
+          result = execute_effect_op::<INSTRUMENTED, _, _, _>(
             state,
             func,
             op,
-            &numified_code.args,
-            &numified_code.funcs,
+            numified_code,
             curr_block,
             &mut next_block_idx,
+            *pos,
           )
-          .map_err(|e| e.add_pos(*pos))?;
+          .map_err(|e| {
+            if INSTRUMENTED {
+              state.record_frame(func, curr_block_idx, i, None, last_label);
+            }
+            e.add_pos(*pos)
+          })?;
         }
       }
+
+      if INSTRUMENTED {
+        state
+          .after_instruction(func, curr_block_idx, i, code, numified_code, taint)
+          .map_err(|e| e.add_pos(code.get_pos()))?;
+      }
     }
+    start_instr = 0;
     if let Some(idx) = next_block_idx {
+      if INSTRUMENTED {
+        state
+          .leave_block()
+          .map_err(|e| e.add_pos(curr_instrs.last().and_then(Instruction::get_pos)))?;
+      }
       curr_block_idx = idx;
     } else {
+      if state.is_speculating() {
+        return Err(
+          InterpError::ReturnWhileSpeculating
+            .add_pos(curr_instrs.last().and_then(Instruction::get_pos)),
+        );
+      }
       return Ok(result);
     }
   }
 }
 
+// Resumes the call stack of a checkpoint. The innermost frame is resumed first and then each caller continues on from the call that it was waiting on.
+fn resume_frames<'a, T: std::io::Write, U: std::io::Write, H: HeapBackend>(
+  state: &mut State<'a, T, U, H>,
+  frames: &[Frame],
+) -> Result<Option<Value>, PositionalInterpError> {
+  let bad_checkpoint =
+    |reason: &str| PositionalInterpError::new(InterpError::BadCheckpoint(reason.to_string()));
+
+  let (frame, callees) = frames
+    .split_first()
+    .ok_or_else(|| bad_checkpoint("no frames found"))?;
+  let func = state
+    .prog
+    .func_index
+    .iter()
+    .find(|f| f.name == frame.func)
+    .ok_or_else(|| PositionalInterpError::new(InterpError::FuncNotFound(frame.func.clone())))?;
+  match func.blocks.get(frame.block) {
+    Some(b) if frame.instr <= b.instrs.len() => {}
+    _ => return Err(bad_checkpoint("frame is outside of its function")),
+  }
+  let last_label = frame
+    .last_label
+    .as_ref()
+    .map(|l| {
+      func
+        .blocks
+        .iter()
+        .find_map(|b| b.label.as_ref().filter(|label| *label == l))
+        .ok_or_else(|| PositionalInterpError::new(InterpError::MissingLabel(l.to_string())))
+    })
+    .transpose()?;
+
+  state.enter_function(func);
+  let mut result = if callees.is_empty() {
+    execute_from::<true, _, _, _>(state, func, frame.block, frame.instr, last_label)
+  } else {
+    resume_frames(state, callees)
+      .inspect_err(|_| {
+        state.record_frame(func, frame.block, frame.instr, frame.dest, last_label);
+      })
+      .and_then(|result| {
+        state.env.pop_frame();
+        if let Some(dest) = frame.dest {
+          state.env.set(dest, result.unwrap());
+        }
+        execute_from::<true, _, _, _>(state, func, frame.block, frame.instr + 1, last_label)
+      })
+  };
+  if let Err(e) = &result {
+    state.dump_on_error(func, e);
+  }
+  if !state.frame_allocs.is_empty() {
+    let freed = free_frame_allocs(state, func);
+    result = result.and_then(|r| freed.map(|()| r));
+  }
+  state.leave_function(func);
+  result
+}
+
+// Parses `s` as an integer that wraps around when it does not fit in an `i64`, or None if it is not an integer at all
+fn parse_wrapping_int(s: &str) -> Option<i64> {
+  let digits = s.strip_prefix(['+', '-']).unwrap_or(s);
+  if digits.is_empty() || !digits.bytes().all(|d| d.is_ascii_digit()) {
+    return None;
+  }
+  let magnitude = digits.bytes().fold(0_i64, |acc, d| {
+    acc.wrapping_mul(10).wrapping_add(i64::from(d - b'0'))
+  });
+  Some(if s.starts_with('-') {
+    magnitude.wrapping_neg()
+  } else {
+    magnitude
+  })
+}
+
 fn parse_args(
   mut env: Environment,
   args: &[bril_rs::Argument],
   args_as_nums: &[usize],
   inputs: &[String],
+  int_literals: IntLiteralPolicy,
 ) -> Result<Environment, InterpError> {
   if args.is_empty() && inputs.is_empty() {
     Ok(env)
@@ -651,14 +1745,22 @@ fn parse_args(
           Ok(())
         }
         bril_rs::Type::Int => {
-          match inputs.get(index).unwrap().parse::<i64>() {
-            Err(_) => {
+          let input = inputs.get(index).unwrap();
+          match (input.parse::<i64>(), parse_wrapping_int(input)) {
+            (Ok(i), _) => env.set(*arg_as_num, Value::Int(i)),
+            // It is an integer, just not one that fits
+            (Err(_), Some(i)) => match int_literals {
+              IntLiteralPolicy::Error => {
+                return Err(InterpError::IntegerLiteralOutOfRange(input.to_string()))
+              }
+              IntLiteralPolicy::Wrap => env.set(*arg_as_num, Value::Int(i)),
+            },
+            (Err(_), None) => {
               return Err(InterpError::BadFuncArgType(
                 bril_rs::Type::Int,
-                (*inputs.get(index).unwrap()).to_string(),
+                input.to_string(),
               ))
             }
-            Ok(i) => env.set(*arg_as_num, Value::Int(i)),
           };
           Ok(())
         }
@@ -674,72 +1776,3034 @@ fn parse_args(
           };
           Ok(())
         }
-        bril_rs::Type::Pointer(..) => unreachable!(),
+        // Only useful with a `SharedHeap`, where the allocation may have been made by another program
+        bril_rs::Type::Pointer(..) => {
+          let input = inputs.get(index).unwrap();
+          let base = input
+            .parse::<usize>()
+            .map_err(|_| InterpError::BadFuncArgType(arg.arg_type.clone(), input.to_string()))?;
+          env.set(*arg_as_num, Value::Pointer(Pointer { base, offset: 0 }));
+          Ok(())
+        }
       })?;
     Ok(env)
   }
 }
 
 // State captures the parts of the interpreter that are used across function boundaries
-struct State<'a, T: std::io::Write> {
+struct State<'a, T: std::io::Write, U: std::io::Write, H: HeapBackend = Heap> {
   prog: &'a BBProgram,
+  options: &'a InterpOptions,
   env: Environment,
-  heap: Heap,
+  heap: CheckedHeap<H>,
   out: T,
+  profiling_out: U,
   instruction_count: u32,
+  // The number of `unique_id` instructions that have been executed, which is the next value that one returns
+  unique_ids: i64,
+  // The instructions counted for `profile_function` and how many of its calls are on the stack
+  profiled_count: u64,
+  profiled_depth: usize,
+  // The number of `id` instructions that copied a variable into itself. Only counted when profiling
+  self_ids: u64,
+  // The number of times that each block, by function and index, was entered and counted by profiling. Only counted for the CSV instruction mix
+  block_counts: FxHashMap<(&'a str, usize), u64>,
+  // Only counted when there is a limit on it
+  block_transitions: u64,
+  // When to stop a run that has been given a timeout, along with the timeout
+  deadline: Option<(std::time::Instant, std::time::Duration)>,
+  // Suspend execution at the first block boundary after this many instructions
+  checkpoint_at: Option<u32>,
+  // The frames of a suspended execution, collected from the innermost frame outwards while unwinding
+  checkpoint_frames: Vec<Frame>,
+  // The names of the functions on the call stack and the number of instructions executed under each distinct stack. Only tracked for the flamegraph
+  call_stack: Vec<&'a str>,
+  folded_stacks: FxHashMap<Vec<&'a str>, u64>,
+  // For each call on the stack, which of its variables have been written and how many. Only tracked for the live slots report
+  written_slots: Vec<(&'a str, Vec<bool>, usize)>,
+  // The position of the instruction that last wrote each slot of the environment, or `None` for a slot that has not been written since its call started, which is an argument. Only tracked for print provenance
+  def_positions: Vec<Option<Option<Position>>>,
+  max_live_slots: FxHashMap<&'a str, usize>,
+  // Only used when taint tracking is turned on
+  taint: Taint,
+  // Only used when looking for dead stores
+  dead_stores: DeadStores<'a>,
+  // Only used when reporting allocations by site
+  alloc_sites: AllocSites<'a>,
+  // Only used when outputting LCOV
+  coverage: Coverage<'a>,
+  // The begin and end of every call, in order, as the phase of the event, the function, and the instruction count. Only used for the Chrome trace
+  trace_events: Vec<(char, &'a str, u32)>,
+  // The cells that have been loaded since they were last stored to. Only tracked when counting redundant loads
+  loaded_cells: FxHashSet<(usize, i64)>,
+  redundant_loads: u64,
+  stored_cells: FxHashSet<(usize, i64)>,
+  store_forwardable_loads: u64,
+  // The types of value that each variable of each function has held. Only tracked when reporting dynamic types
+  dynamic_types: FxHashMap<(&'a str, &'a str), BTreeSet<&'static str>>,
+  // The callee and return value of every call that produces a value, in the order that the calls were made
+  call_returns: Vec<(String, Option<Value>)>,
+  // The function that returned each allocation that was returned by a call and has not been freed yet, by base. Only tracked when looking for dangling returns
+  returned_bases: FxHashMap<usize, String>,
+  // The number of `add`, `sub` and `mul` instructions that wrapped around. Only counted when warning about them
+  wraparounds: u64,
+  // Whether the state has already been output for the error that is unwinding the stack
+  dumped_on_error: bool,
+  // Called at each `debugbreak`
+  on_break: Option<&'a mut dyn FnMut(&Breakpoint) -> Resume>,
+  // Called before each instruction
+  on_step: Option<&'a mut dyn FnMut(&Breakpoint) -> Resume>,
+  // The function and the position of the current instruction of each call on the stack. Only tracked when there is a callback to hand it to
+  frames: Vec<(&'a str, Option<Position>)>,
+  // Replaces the built-in arithmetic and comparisons when given
+  semantics: Option<&'a mut dyn Semantics>,
+  // What calls of functions that the program does not define are given to
+  natives: Option<&'a NativeFunctions>,
+  // Each `speculate` that has not been committed or aborted yet, innermost last
+  speculations: Vec<Speculation>,
+  // Rewrites each line that `print` outputs
+  on_print: Option<&'a mut dyn FnMut(&str) -> String>,
+  // For `print_throttle`: the window of the logical clock that lines are being counted in, how many of them have been output in it, the lines held back by `ThrottlePolicy::Block` and how many were dropped by `ThrottlePolicy::Drop`
+  throttle_window: u32,
+  throttle_printed: u32,
+  held_lines: VecDeque<String>,
+  dropped_lines: u64,
+  // The number of lines written to `out` since it was last flushed
+  unflushed_prints: u32,
+  // The allocations made by `alloca` that have not been freed at the end of their call yet, as the depth of the call and the base, in the order that they were made
+  //
+  // Every allocation has one of two lifetime classes: a heap allocation, made by `alloc`, `dup` or `memdup`, lives until it is freed, and a frame allocation, made by `alloca`, lives until the call that made it returns. So a frame allocation outlives those made by the calls below it and every heap allocation outlives every frame allocation
+  frame_allocs: Vec<(usize, usize)>,
+  // What calls return instead of executing their callee, when running a function with stubs, and the calls that were made to them
+  stubs: Option<&'a FxHashMap<String, Option<bril_rs::Literal>>>,
+  stubbed_calls: Vec<StubbedCall>,
+  // The functions that `detect_infinite_recursion` applies to
+  pure_funcs: FxHashSet<usize>,
+  // The calls of pure functions that have not returned yet, as the function and the keys of its arguments, with a set of them for finding a call that recurs
+  pure_calls: Vec<(usize, Vec<Option<ValueKey>>)>,
+  active_pure_calls: FxHashSet<(usize, Vec<Option<ValueKey>>)>,
+}
+
+// What is needed to abort a speculation
+struct Speculation {
+  // The depth of the call that started it, which is the only one that can commit or abort it
+  depth: usize,
+  // The variables of that frame when it started
+  vars: Vec<Value>,
+  // Everything printed since it started
+  output: Vec<u8>,
 }
 
-impl<'a, T: std::io::Write> State<'a, T> {
-  fn new(prog: &'a BBProgram, env: Environment, heap: Heap, out: T) -> Self {
+impl<'a, T: std::io::Write, U: std::io::Write, H: HeapBackend> State<'a, T, U, H> {
+  fn new(
+    prog: &'a BBProgram,
+    options: &'a InterpOptions,
+    mut env: Environment,
+    heap: CheckedHeap<H>,
+    out: T,
+    profiling_out: U,
+  ) -> Self {
+    env.poison = options.poison_frames;
     Self {
       prog,
+      options,
       env,
       heap,
       out,
+      profiling_out,
       instruction_count: 0,
+      unique_ids: 0,
+      profiled_count: 0,
+      self_ids: 0,
+      block_counts: FxHashMap::default(),
+      profiled_depth: 0,
+      block_transitions: 0,
+      deadline: None,
+      checkpoint_at: None,
+      checkpoint_frames: Vec::new(),
+      call_stack: Vec::new(),
+      folded_stacks: FxHashMap::default(),
+      written_slots: Vec::new(),
+      def_positions: Vec::new(),
+      max_live_slots: FxHashMap::default(),
+      taint: Taint::default(),
+      dead_stores: DeadStores::default(),
+      alloc_sites: AllocSites::default(),
+      coverage: Coverage::default(),
+      trace_events: Vec::new(),
+      loaded_cells: FxHashSet::default(),
+      redundant_loads: 0,
+      stored_cells: FxHashSet::default(),
+      store_forwardable_loads: 0,
+      dynamic_types: FxHashMap::default(),
+      call_returns: Vec::new(),
+      returned_bases: FxHashMap::default(),
+      wraparounds: 0,
+      dumped_on_error: false,
+      on_break: None,
+      on_step: None,
+      frames: Vec::new(),
+      semantics: None,
+      natives: None,
+      speculations: Vec::new(),
+      on_print: None,
+      throttle_window: 0,
+      throttle_printed: 0,
+      held_lines: VecDeque::new(),
+      dropped_lines: 0,
+      unflushed_prints: 0,
+      frame_allocs: Vec::new(),
+      stubs: None,
+      stubbed_calls: Vec::new(),
+      pure_funcs: if options.detect_infinite_recursion {
+        prog.pure_functions()
+      } else {
+        FxHashSet::default()
+      },
+      pure_calls: Vec::new(),
+      active_pure_calls: FxHashSet::default(),
     }
   }
-}
 
-/// The entrance point to the interpreter. It runs over a ```prog```:[`BBProgram`] starting at the "main" function with ```input_args``` as input. Print statements output to ```out``` which implements [std::io::Write]. You also need to include whether you want the interpreter to count the number of instructions run with ```profiling```. This information is outputted to [std::io::stderr]
-pub fn execute_main<T: std::io::Write, U: std::io::Write>(
-  prog: &BBProgram,
-  out: T,
-  input_args: &[String],
-  profiling: bool,
-  mut profiling_out: U,
-) -> Result<(), PositionalInterpError> {
-  let main_func = prog
-    .index_of_main
-    .map(|i| prog.get(i).unwrap())
-    .ok_or_else(|| PositionalInterpError::new(InterpError::NoMainFunction))?;
+  // Writes a line that `print` outputs, which is held back if a speculation may still abort it
+  fn emit(&mut self, line: &str) -> Result<(), InterpError> {
+    if let Some(speculation) = self.speculations.last_mut() {
+      speculation.output.extend(line.bytes());
+      return Ok(());
+    }
+    self.write_out(line.as_bytes(), 1)
+  }
 
-  if main_func.return_type.is_some() {
-    return Err(InterpError::NonEmptyRetForFunc(main_func.name.clone()))
-      .map_err(|e| e.add_pos(main_func.pos));
+  // Writes `lines` lines of output to `out` and flushes it as `output_buffering` and `flush_every` ask
+  fn write_out(&mut self, bytes: &[u8], lines: u32) -> Result<(), InterpError> {
+    self
+      .out
+      .write_all(bytes)
+      .map_err(|e| InterpError::IoError(Box::new(e)))?;
+    if self.options.output_buffering == OutputBuffering::End {
+      return Ok(());
+    }
+    self.unflushed_prints += lines;
+    if self.unflushed_prints
+      >= self
+        .options
+        .flush_every
+        .map_or(1, std::num::NonZeroU32::get)
+    {
+      self.unflushed_prints = 0;
+      // We call flush here in case `out` is a https://doc.rust-lang.org/std/io/struct.BufWriter.html
+      // Otherwise we would expect this flush to be a nop.
+      self
+        .out
+        .flush()
+        .map_err(|e| InterpError::IoError(Box::new(e)))?;
+    }
+    Ok(())
   }
 
-  let mut env = Environment::new(main_func.num_of_vars);
-  let heap = Heap::default();
+  // Moves on to the window of `print_throttle` that the logical clock is in and outputs as many of the held back lines as it allows
+  fn release_held_lines(&mut self, throttle: PrintThrottle) -> Result<(), InterpError> {
+    let window = self.instruction_count / throttle.window;
+    if window != self.throttle_window {
+      self.throttle_window = window;
+      self.throttle_printed = 0;
+    }
+    while self.throttle_printed < throttle.lines {
+      let Some(line) = self.held_lines.pop_front() else {
+        break;
+      };
+      self.throttle_printed += 1;
+      self.emit(&line)?;
+    }
+    Ok(())
+  }
+
+  // Outputs a line that `print` outputs if `print_throttle` allows it and otherwise drops it or holds it back
+  fn throttled_print(&mut self, line: String) -> Result<(), InterpError> {
+    let Some(throttle) = self.options.print_throttle else {
+      return self.emit(&line);
+    };
+    self.release_held_lines(throttle)?;
+    // Held back lines go first so that the output stays in order
+    if self.held_lines.is_empty() && self.throttle_printed < throttle.lines {
+      self.throttle_printed += 1;
+      self.emit(&line)
+    } else {
+      match throttle.policy {
+        ThrottlePolicy::Drop => self.dropped_lines += 1,
+        ThrottlePolicy::Block => self.held_lines.push_back(line),
+      }
+      Ok(())
+    }
+  }
+
+  // Whether the innermost speculation was started by the current call
+  fn is_speculating(&self) -> bool {
+    self
+      .speculations
+      .last()
+      .is_some_and(|s| s.depth == self.env.stack_pointers.len())
+  }
+
+  #[inline(always)]
+  fn enter_function(&mut self, func: &'a BBFunction) {
+    if self.is_profiled(func) {
+      self.profiled_depth += 1;
+    }
+    if self.options.lcov.is_some() {
+      self.coverage.enter_function(func);
+    }
+    if self.options.flamegraph {
+      self.call_stack.push(&func.name);
+    }
+    if self.options.chrome_trace {
+      self
+        .trace_events
+        .push(('B', &func.name, self.instruction_count));
+    }
+    if self.tracks_frames() {
+      self.frames.push((&func.name, None));
+    }
+    if self.options.dynamic_types {
+      func
+        .args_as_nums
+        .iter()
+        .for_each(|a| self.record_dynamic_type(func, *a));
+    }
+    if self.options.print_provenance {
+      let start = self.env.current_pointer;
+      let end = start + func.num_of_vars;
+      if self.def_positions.len() < end {
+        self.def_positions.resize(end, None);
+      }
+      self.def_positions[start..end].fill(None);
+    }
+    if self.options.live_slots {
+      let mut written = vec![false; func.num_of_vars];
+      func.args_as_nums.iter().for_each(|a| written[*a] = true);
+      self
+        .written_slots
+        .push((&func.name, written, func.args_as_nums.len()));
+    }
+  }
 
-  env = parse_args(env, &main_func.args, &main_func.args_as_nums, input_args)
-    .map_err(|e| e.add_pos(main_func.pos))?;
+  #[inline(always)]
+  fn leave_function(&mut self, func: &'a BBFunction) {
+    if self.is_profiled(func) {
+      self.profiled_depth -= 1;
+    }
+    if self.options.flamegraph {
+      self.call_stack.pop();
+    }
+    if self.options.chrome_trace {
+      self
+        .trace_events
+        .push(('E', &func.name, self.instruction_count));
+    }
+    if self.tracks_frames() {
+      self.frames.pop();
+    }
+    if self.options.live_slots {
+      let (name, _, count) = self.written_slots.pop().unwrap();
+      let max = self.max_live_slots.entry(name).or_default();
+      *max = (*max).max(count);
+    }
+  }
 
-  let mut state = State::new(prog, env, heap, out);
+  #[inline(always)]
+  const fn tracks_frames(&self) -> bool {
+    self.on_break.is_some() || self.on_step.is_some()
+  }
 
-  execute(&mut state, main_func)?;
+  // Whether anything needs to look at each block, instruction, or call as the program runs. When nothing does, the copy of the interpreter without that bookkeeping is run
+  fn instrumented(&self) -> bool {
+    let o = self.options;
+    self.tracks_frames()
+      || self.semantics.is_some()
+      || self.checkpoint_at.is_some()
+      || self.deadline.is_some()
+      || o.arithmetic != ArithmeticMode::Wrapping
+      || !o.arithmetic_overrides.is_empty()
+      || o.wraparound_warnings
+      || o.profile_function.is_some()
+      || (o.profiling && o.profile_format == ProfileFormat::Csv)
+      || o.lcov.is_some()
+      || o.flamegraph
+      || o.chrome_trace
+      || o.trace_blocks
+      || o.print_throttle.is_some()
+      || o.max_block_transitions.is_some()
+      || o.strict_undef
+      || o.strict_uninit
+      || o.poison_frames
+      || o.dead_stores
+      || o.taint
+      || o.alloc_sites
+      || o.live_slots
+      || o.print_provenance
+      || o.redundant_loads
+      || o.store_forwardable_loads
+      || o.dynamic_types
+      || o.trace_memory
+      || o.access_log
+      || o.trace_instructions
+      || o.detect_infinite_recursion
+      || o.dump_on_error
+  }
 
-  if !state.heap.is_empty() {
-    return Err(InterpError::MemLeak).map_err(|e| e.add_pos(main_func.pos));
+  // The behaviour of `op` on overflow, which can only be wrapping when the interpreter is not instrumented
+  #[inline(always)]
+  fn arithmetic_for<const INSTRUMENTED: bool>(&self, op: bril_rs::ValueOps) -> ArithmeticMode {
+    if INSTRUMENTED {
+      self.options.arithmetic_for(op)
+    } else {
+      ArithmeticMode::Wrapping
+    }
   }
 
-  if profiling {
-    writeln!(profiling_out, "total_dyn_inst: {}", state.instruction_count)
-      // We call flush here in case `profiling_out` is a https://doc.rust-lang.org/std/io/struct.BufWriter.html
-      // Otherwise we would expect this flush to be a nop.
-      .and_then(|_| profiling_out.flush())
-      .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  // Called after the instructions of the block `block` of `func` have been counted, before any of them run
+  fn enter_block(&mut self, func: &'a BBFunction, block: usize) -> Result<(), InterpError> {
+    let len = func.blocks[block].instrs.len() as u64;
+    if self.options.profile_function.is_some() {
+      self.record_profiled_instructions(func, len);
+    }
+    if self.options.profiling && self.options.profile_format == ProfileFormat::Csv {
+      self.record_block(func, block);
+    }
+    if self.options.lcov.is_some() {
+      self.coverage.enter_block(func, block);
+    }
+    if self.options.flamegraph {
+      self.record_stack_sample(len);
+    }
+    if self.options.trace_blocks {
+      self.trace_block(func, block)?;
+    }
+    if !self.held_lines.is_empty() {
+      // Held back lines are released as time passes even when nothing is being printed
+      self.release_held_lines(self.options.print_throttle.unwrap())?;
+    }
+    Ok(())
   }
 
-  Ok(())
+  // Called when moving on from a block to the next one
+  fn leave_block(&mut self) -> Result<(), InterpError> {
+    if let Some(limit) = self.options.max_block_transitions {
+      self.block_transitions += 1;
+      if self.block_transitions > limit {
+        return Err(InterpError::BlockTransitionLimitExceeded(limit));
+      }
+    }
+    // Any run that does not finish has to keep moving between blocks, so this is the only place that needs to look at the time
+    if let Some((deadline, timeout)) = self.deadline {
+      if std::time::Instant::now() >= deadline {
+        return Err(InterpError::TimedOut(timeout));
+      }
+    }
+    Ok(())
+  }
+
+  // Called before `code` runs. Returns whether its arguments are tainted when taint tracking is on
+  fn before_instruction(
+    &mut self,
+    func: &BBFunction,
+    code: &Instruction,
+    numified_code: &NumifiedInstruction,
+    last_label: Option<&String>,
+  ) -> Result<Option<bool>, InterpError> {
+    if self.tracks_frames() {
+      self.frames.last_mut().unwrap().1 = code.get_pos();
+    }
+    if let Some(on_step) = self.on_step.as_mut() {
+      if on_step(&breakpoint(&self.env, &self.frames, func, code.get_pos())) == Resume::Abort {
+        return Err(InterpError::AbortedAtBreakpoint);
+      }
+    }
+
+    if (self.options.strict_undef || self.options.strict_uninit || self.options.poison_frames)
+      && !matches!(
+        code,
+        Instruction::Value {
+          op: bril_rs::ValueOps::Phi,
+          ..
+        }
+      )
+    {
+      if let Some(a) = numified_code
+        .args
+        .iter()
+        .find(|a| matches!(self.env.get(a), Value::Uninitialized))
+      {
+        // A variable past the end of the frame can only come from a bug in numbering the variables
+        let name = func
+          .var_names
+          .get(*a)
+          .cloned()
+          .unwrap_or_else(|| format!("#{a}"));
+        return Err(InterpError::UsingUndefinedValue(name));
+      }
+    }
+
+    if self.options.dead_stores {
+      self.dead_stores.before(&self.env, numified_code);
+    }
+
+    if self.options.taint {
+      self
+        .taint
+        .before(&self.env, code, numified_code, last_label)
+        .map(Some)
+    } else {
+      Ok(None)
+    }
+  }
+
+  // Called after `code`, the instruction `instr` of the block `block` of `func`, has run. `taint` is what `before_instruction` returned
+  fn after_instruction(
+    &mut self,
+    func: &'a BBFunction,
+    block: usize,
+    instr: usize,
+    code: &Instruction,
+    numified_code: &NumifiedInstruction,
+    taint: Option<bool>,
+  ) -> Result<(), InterpError> {
+    if let Some(taint) = taint {
+      self.taint.after(&self.env, code, numified_code, taint);
+    }
+    if self.options.dead_stores {
+      self
+        .dead_stores
+        .after(&self.env, func, block, instr, numified_code, code.get_pos());
+    }
+    if self.options.alloc_sites {
+      self
+        .alloc_sites
+        .after(&self.env, func, block, instr, code, numified_code);
+    }
+    if self.options.live_slots {
+      if let Some(dest) = numified_code.dest {
+        self.record_write(dest);
+      }
+    }
+    if self.options.print_provenance {
+      if let Some(dest) = numified_code.dest {
+        self.record_definition(dest, code.get_pos());
+      }
+    }
+    if self.options.redundant_loads || self.options.store_forwardable_loads {
+      self.record_redundant_load(code, numified_code);
+    }
+    if self.options.dynamic_types {
+      if let Some(dest) = numified_code.dest {
+        self.record_dynamic_type(func, dest);
+      }
+    }
+    if self.options.trace_memory {
+      self.trace_memory(code, numified_code)?;
+    }
+    if self.options.access_log {
+      self.log_accesses(code, numified_code)?;
+    }
+    if self.options.trace_instructions {
+      self.trace_instruction(func, code, numified_code)?;
+    }
+    Ok(())
+  }
+
+  #[inline(always)]
+  fn is_profiled(&self, func: &BBFunction) -> bool {
+    self.options.profile_function.as_ref() == Some(&func.name)
+  }
+
+  // Attributes `n` instructions executed in `func` to `profile_function` if they should be counted
+  #[inline(always)]
+  fn record_profiled_instructions(&mut self, func: &BBFunction, n: u64) {
+    if self.profiled_depth > 0 && (self.options.profile_callees || self.is_profiled(func)) {
+      self.profiled_count += n;
+    }
+  }
+
+  // Counts an entry into the block `block` of `func` for the instruction mix, if profiling counts it
+  fn record_block(&mut self, func: &'a BBFunction, block: usize) {
+    if self.options.profile_function.is_none()
+      || (self.profiled_depth > 0 && (self.options.profile_callees || self.is_profiled(func)))
+    {
+      *self.block_counts.entry((&func.name, block)).or_default() += 1;
+    }
+  }
+
+  // Marks `var` as written in the current call
+  fn record_write(&mut self, var: usize) {
+    let (_, written, count) = self.written_slots.last_mut().unwrap();
+    if !written[var] {
+      written[var] = true;
+      *count += 1;
+    }
+  }
+
+  // Adds the type of the current value of `var` to the types that it has held
+  fn record_dynamic_type(&mut self, func: &'a BBFunction, var: usize) {
+    let ty = match self.env.get(&var) {
+      Value::Uninitialized => return,
+      v => v.type_name(),
+    };
+    self
+      .dynamic_types
+      .entry((&func.name, &func.var_names[var]))
+      .or_default()
+      .insert(ty);
+  }
+
+  // Attributes the `n` instructions that have just been counted to the current call stack
+  fn record_stack_sample(&mut self, n: u64) {
+    let n = match self.options.flamegraph_interval {
+      // The number of multiples of the interval that these instructions crossed
+      Some(interval) => {
+        let after = u64::from(self.instruction_count);
+        after / interval.get() - (after - n) / interval.get()
+      }
+      None => n,
+    };
+    if n == 0 {
+      return;
+    }
+    match self.folded_stacks.get_mut(self.call_stack.as_slice()) {
+      Some(count) => *count += n,
+      None => {
+        self.folded_stacks.insert(self.call_stack.clone(), n);
+      }
+    }
+  }
+
+  // Counts a `load` of a cell that has already been loaded and not stored to since, and one of a cell whose last access was a `store`. Called once `instr` has executed successfully.
+  // Freed cells are not forgotten since the base of an allocation is never reused
+  fn record_redundant_load(&mut self, instr: &Instruction, numified: &NumifiedInstruction) {
+    use bril_rs::{EffectOps, ValueOps};
+    let p: &Pointer = match instr {
+      Instruction::Value {
+        op: ValueOps::Load, ..
+      }
+      | Instruction::Effect {
+        op: EffectOps::Store,
+        ..
+      } => self.env.get(&numified.args[0]).into(),
+      _ => return,
+    };
+    let cell = (p.base, p.offset);
+    if matches!(instr, Instruction::Effect { .. }) {
+      self.loaded_cells.remove(&cell);
+      self.stored_cells.insert(cell);
+    } else {
+      if !self.loaded_cells.insert(cell) {
+        self.redundant_loads += 1;
+      }
+      if self.stored_cells.remove(&cell) {
+        self.store_forwardable_loads += 1;
+      }
+    }
+  }
+
+  // Writes a line for entering the block `idx` of `func`
+  fn trace_block(&mut self, func: &BBFunction, idx: usize) -> Result<(), InterpError> {
+    let label = func.blocks[idx]
+      .label
+      .as_ref()
+      .map_or_else(String::new, |l| format!(" .{l}"));
+    writeln!(self.profiling_out, "block @{} {idx}{label}", func.name)
+      .and_then(|_| self.profiling_out.flush())
+      .map_err(|e| InterpError::IoError(Box::new(e)))
+  }
+
+  // Writes a line with `instr` and the value that it assigned. Called once it has executed successfully
+  fn trace_instruction(
+    &mut self,
+    func: &BBFunction,
+    instr: &Instruction,
+    numified: &NumifiedInstruction,
+  ) -> Result<(), InterpError> {
+    match numified.dest {
+      Some(dest) => writeln!(
+        self.profiling_out,
+        "@{}: {instr} => {}",
+        func.name,
+        self.env.get(&dest)
+      ),
+      None => writeln!(self.profiling_out, "@{}: {instr}", func.name),
+    }
+    .and_then(|_| self.profiling_out.flush())
+    .map_err(|e| InterpError::IoError(Box::new(e)))
+  }
+
+  // Writes a line describing what `instr` did if it is a memory operation. Called once it has executed successfully
+  fn trace_memory(
+    &mut self,
+    instr: &Instruction,
+    numified: &NumifiedInstruction,
+  ) -> Result<(), InterpError> {
+    use bril_rs::{EffectOps, ValueOps};
+    let args = &numified.args;
+    let pointer = |var: &usize| -> &Pointer { self.env.get(var).into() };
+    let event = match instr {
+      Instruction::Value {
+        op: op @ (ValueOps::Alloc | ValueOps::Alloca),
+        ..
+      } => format!(
+        "{op} base={} size={}",
+        pointer(&numified.dest.unwrap()).base,
+        get_arg::<i64>(&self.env, 0, args)
+      ),
+      Instruction::Value {
+        op: ValueOps::Dup, ..
+      } => {
+        let src = pointer(&args[0]);
+        format!(
+          "dup base={} size={} from base={} offset={}",
+          pointer(&numified.dest.unwrap()).base,
+          get_arg::<i64>(&self.env, 1, args),
+          src.base,
+          src.offset
+        )
+      }
+      Instruction::Value {
+        op: ValueOps::Memdup,
+        ..
+      } => {
+        let base = pointer(&numified.dest.unwrap()).base;
+        format!(
+          "memdup base={base} size={} from base={}",
+          self.heap.block(base).map_or(0, <[Value]>::len),
+          pointer(&args[0]).base
+        )
+      }
+      Instruction::Value {
+        op: ValueOps::Load, ..
+      } => {
+        let p = pointer(&args[0]);
+        format!("load base={} offset={}", p.base, p.offset)
+      }
+      Instruction::Effect {
+        op: EffectOps::Store,
+        ..
+      } => {
+        let p = pointer(&args[0]);
+        format!("store base={} offset={}", p.base, p.offset)
+      }
+      Instruction::Effect {
+        op: EffectOps::Free,
+        ..
+      } => format!("free base={}", pointer(&args[0]).base),
+      _ => return Ok(()),
+    };
+    let pos = at_pos(instr.get_pos());
+    writeln!(self.profiling_out, "{event}{pos}")
+      .and_then(|_| self.profiling_out.flush())
+      .map_err(|e| InterpError::IoError(Box::new(e)))
+  }
+
+  // Outputs a line for each heap cell that was read or written by `instr`, `R base offset` or `W base offset`
+  fn log_accesses(
+    &mut self,
+    instr: &Instruction,
+    numified: &NumifiedInstruction,
+  ) -> Result<(), InterpError> {
+    use bril_rs::{EffectOps, ValueOps};
+    let args = &numified.args;
+    let pointer = |var: &usize| -> &Pointer { self.env.get(var).into() };
+    let mut accesses = Vec::new();
+    match instr {
+      Instruction::Value {
+        op: ValueOps::Load, ..
+      } => accesses.push(('R', pointer(&args[0]).clone())),
+      Instruction::Effect {
+        op: EffectOps::Store,
+        ..
+      } => accesses.push(('W', pointer(&args[0]).clone())),
+      Instruction::Value {
+        op: ValueOps::Dup, ..
+      } => {
+        // Each cell is read and then written to the new allocation
+        let (src, dest) = (pointer(&args[0]), pointer(&numified.dest.unwrap()));
+        for i in 0..get_arg::<i64>(&self.env, 1, args) {
+          accesses.push(('R', src.add(i)));
+          accesses.push(('W', dest.add(i)));
+        }
+      }
+      Instruction::Value {
+        op: ValueOps::Memdup,
+        ..
+      } => {
+        let (src, dest) = (pointer(&args[0]), pointer(&numified.dest.unwrap()));
+        for i in 0..self.heap.block(dest.base).map_or(0, <[Value]>::len) as i64 {
+          accesses.push(('R', src.add(i)));
+          accesses.push(('W', dest.add(i)));
+        }
+      }
+      _ => return Ok(()),
+    }
+    accesses
+      .iter()
+      .try_for_each(|(kind, p)| writeln!(self.profiling_out, "{kind} {} {}", p.base, p.offset))
+      .and_then(|_| self.profiling_out.flush())
+      .map_err(|e| InterpError::IoError(Box::new(e)))
+  }
+
+  // Outputs the variables of `func` and the contents of the heap to `profiling_out` when `e` is the first error to reach a frame. The frame of the call that failed has not been popped yet so it is still the current one.
+  // A failure to write is ignored so that it does not hide `e`
+  #[cold]
+  fn dump_on_error(&mut self, func: &BBFunction, e: &PositionalInterpError) {
+    if !self.options.dump_on_error || self.dumped_on_error || !self.checkpoint_frames.is_empty() {
+      return;
+    }
+    self.dumped_on_error = true;
+
+    let cell = |v: &Value| match v {
+      Value::Uninitialized => "uninitialized".to_string(),
+      v => v.to_string(),
+    };
+    let pos = at_pos(e.pos());
+    let mut vars: Vec<_> = (0..func.num_of_vars)
+      .map(|v| (&func.var_names[v], self.env.get(&v)))
+      .collect();
+    vars.sort_unstable_by_key(|(name, _)| *name);
+
+    let _ = writeln!(self.profiling_out, "error in @{}{pos}", func.name)
+      .and_then(|_| writeln!(self.profiling_out, "variables:"))
+      .and_then(|_| {
+        vars
+          .iter()
+          .try_for_each(|(name, v)| writeln!(self.profiling_out, "  {name} = {}", cell(v)))
+      })
+      .and_then(|_| writeln!(self.profiling_out, "heap:"))
+      .and_then(|_| {
+        self.heap.bases().into_iter().try_for_each(|base| {
+          let cells: Vec<_> = self.heap.block(base).unwrap().iter().map(cell).collect();
+          writeln!(self.profiling_out, "  {base}: [{}]", cells.join(", "))
+        })
+      })
+      .and_then(|_| self.profiling_out.flush());
+  }
+
+  // Suspends execution at `frame`. The returned error unwinds the call stack so that each caller can record its own frame
+  fn start_checkpoint(&mut self, frame: Frame) -> PositionalInterpError {
+    self.checkpoint_at = None;
+    self.checkpoint_frames.push(frame);
+    PositionalInterpError::new(InterpError::Checkpointed)
+  }
+
+  // Called on the error path of an instruction. If we are unwinding to take a checkpoint, then the instruction is a call that should be resumed once its callee finishes
+  #[cold]
+  fn record_frame(
+    &mut self,
+    func: &BBFunction,
+    block: usize,
+    instr: usize,
+    dest: Option<usize>,
+    last_label: Option<&String>,
+  ) {
+    if !self.checkpoint_frames.is_empty() {
+      self.checkpoint_frames.push(Frame {
+        func: func.name.clone(),
+        block,
+        instr,
+        last_label: last_label.cloned(),
+        dest,
+      });
+    }
+  }
+}
+
+/// How integer operations behave when their result does not fit in an `int`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArithmeticMode {
+  /// Results wrap around using two's complement like in brili
+  #[default]
+  Wrapping,
+  /// An overflowing `add`, `sub`, `mul`, `gcd` or `lcm` is reported as an error
+  Checked,
+}
+
+/// How the results of `fadd`, `fsub`, `fmul` and `fdiv` are rounded
+///
+/// Modes other than [`RoundingMode::Nearest`] are emulated by adjusting the round to nearest result after each operation. This is exact for finite results but a result that overflowed to infinity stays infinite even when the mode would round it to the largest finite float.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RoundingMode {
+  /// Round to nearest, ties to even, which is what the hardware does
+  #[default]
+  Nearest,
+  /// Round toward zero
+  TowardZero,
+  /// Round toward positive infinity
+  TowardPositive,
+  /// Round toward negative infinity
+  TowardNegative,
+}
+
+/// What happens to an integer literal, in the program or in the arguments of "main", that is outside of the range of an `int`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IntLiteralPolicy {
+  /// Report an error
+  #[default]
+  Error,
+  /// Wrap around into the range using two's complement, i.e. `9223372036854775808` becomes `-9223372036854775808`
+  Wrap,
+}
+
+/// How `div` rounds a quotient that is not a whole number. The two only differ when exactly one operand is negative, i.e. `-7 / 2`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DivisionMode {
+  /// Round toward zero, giving `-3`. This matches brili, C, and Rust
+  #[default]
+  Truncating,
+  /// Round toward negative infinity, giving `-4`. This matches `//` in Python
+  Flooring,
+}
+
+/// How a comparison handles arguments that are not of the type that it takes, such as `eq` of an `int` and a `float`. This can only happen in a program that is run without ```check::type_check```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ComparisonPolicy {
+  /// Report an error
+  #[default]
+  Strict,
+  /// Compare ints and floats by their numeric values, so `eq` and `feq` both find `1` and `1.0` equal. Any other mix of types is still an error
+  Numeric,
+}
+
+/// How ```InterpOptions::profiling``` is output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProfileFormat {
+  /// The total number of instructions, i.e. `total_dyn_inst: 12`, followed by the lines of the options that add to it
+  #[default]
+  Text,
+  /// The number of instructions executed with each opcode as CSV, for spreadsheets and other analysis tools. The first row is always the header `opcode,count`, followed by a row for each opcode that was executed in alphabetical order, i.e. `add,3`, and a last row with the total, i.e. `total,12`. ```profile_function``` limits which instructions are counted as it does for the text. Only the CSV is output, so ```self_ids``` and ```wraparound_warnings``` do not add their counts. Calls resumed from a [`Checkpoint`] do not count the rest of the block that they were resumed in
+  Csv,
+}
+
+/// What ```InterpOptions::print_throttle``` does with the lines that `print` outputs beyond its limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ThrottlePolicy {
+  /// Discard them. The number of lines that were discarded is output to `profiling_out` at the end of execution, i.e. `dropped_lines: 12`
+  #[default]
+  Drop,
+  /// Hold them back and output them in order, no more than the limit in each later window, so that no line is lost but the output falls further behind the program. Lines that are still held back when execution stops, even with an error, are output then. A program that keeps printing faster than the limit holds on to more and more lines
+  Block,
+}
+
+/// When `print` flushes `out`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputBuffering {
+  /// After every line, or every ```InterpOptions::flush_every``` lines, so that the output keeps up with what the program does
+  #[default]
+  Line,
+  /// Only once execution stops, even with an error, so that a buffered `out` such as a [`std::io::BufWriter`] writes its output in as few writes as it can. ```InterpOptions::flush_every``` is ignored. This is faster for programs that print a lot, but the output can come long after the warnings and errors written to `profiling_out` that followed it. `out` should be buffered since it is written to as often as before
+  ///
+  /// ```
+  /// use brilirs::{basic_block::BBProgram, interp};
+  /// use bril_rs::Program;
+  ///
+  /// // Counts the writes that reach it
+  /// #[derive(Debug, Default)]
+  /// struct Writes {
+  ///   written: Vec<u8>,
+  ///   writes: usize,
+  /// }
+  /// impl std::io::Write for Writes {
+  ///   fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+  ///     self.written.extend(buf);
+  ///     self.writes += 1;
+  ///     Ok(buf.len())
+  ///   }
+  ///   fn flush(&mut self) -> std::io::Result<()> {
+  ///     Ok(())
+  ///   }
+  /// }
+  ///
+  /// // What a speculation prints is output when it commits
+  /// let src = "@main {\n  a: int = const 1;\n  print a;\n  speculate;\n  print a;\n  commit;\n  print a;\n  speculate;\n  print a;\n  commit;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  ///
+  /// let writes = |output_buffering| {
+  ///   let options = interp::InterpOptions {
+  ///     output_buffering,
+  ///     ..Default::default()
+  ///   };
+  ///   let mut out = std::io::BufWriter::new(Writes::default());
+  ///   interp::execute_main_with(&bbprog, &mut out, &[], std::io::sink(), &options).unwrap();
+  ///   let out = out.into_inner().unwrap();
+  ///   assert_eq!(out.written, b"1\n1\n1\n1\n");
+  ///   out.writes
+  /// };
+  /// assert_eq!(writes(interp::OutputBuffering::Line), 4);
+  /// assert_eq!(writes(interp::OutputBuffering::End), 1);
+  /// ```
+  End,
+}
+
+/// A limit on how many lines `print` outputs in a span of time, for when the output is being watched as it is produced
+///
+/// Time is the logical clock read by `clock`, the number of instructions executed so far, so a throttled run outputs the same lines on every machine. Execution is split into windows of ```window``` instructions and at most ```lines``` lines are output in each one. Since instructions are counted a whole block at a time when it is entered, a block that crosses into a new window counts all of its prints in the new one
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp};
+/// use bril_rs::Program;
+///
+/// // Prints 0 to 9, taking 4 instructions for each one
+/// let src = "@main {\n  i: int = const 0;\n  one: int = const 1;\n  ten: int = const 10;\n.loop:\n  print i;\n  i: int = add i one;\n  c: bool = lt i ten;\n  br c .loop .done;\n.done:\n}";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+///
+/// let run = |policy| {
+///   let throttle = interp::PrintThrottle { lines: 1, window: 10.try_into().unwrap(), policy };
+///   let options = interp::InterpOptions { print_throttle: Some(throttle), ..Default::default() };
+///   let (mut out, mut report) = (Vec::new(), Vec::new());
+///   interp::execute_main_with(&bbprog, &mut out, &[], &mut report, &options).unwrap();
+///   (String::from_utf8(out).unwrap(), String::from_utf8(report).unwrap())
+/// };
+/// // At most one line for every 10 instructions
+/// assert_eq!(
+///   run(interp::ThrottlePolicy::Drop),
+///   ("0\n1\n4\n6\n9\n".to_string(), "dropped_lines: 5\n".to_string())
+/// );
+/// // Every line, some of them only once the program has finished
+/// assert_eq!(
+///   run(interp::ThrottlePolicy::Block).0,
+///   "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n"
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrintThrottle {
+  /// The most lines to output in each window
+  pub lines: u32,
+  /// The number of instructions in each window
+  pub window: std::num::NonZeroU32,
+  /// What to do with the lines that do not fit in their window
+  pub policy: ThrottlePolicy,
+}
+
+/// The state of the interpreter when it reaches a `debugbreak`, which is handed to the callback given to [`execute_main_with_breakpoints`], or before each instruction with [`execute_main_stepping`]
+///
+/// The variables and the backtrace are only put together when they are asked for, so a callback that is called before every instruction only pays for them where it stops
+#[derive(Debug)]
+pub struct Breakpoint<'a> {
+  /// The function containing the instruction
+  pub func: &'a str,
+  /// The position of the instruction in the source, if it is known
+  pub pos: Option<Position>,
+  env: &'a Environment,
+  var_names: &'a [String],
+  frames: &'a [(&'a str, Option<Position>)],
+}
+
+impl<'a> Breakpoint<'a> {
+  /// The variables of the current frame that have a value and their values as `print` would show them, in the order they first appear in the function. A variable which has not been assigned yet in this call may show a value left over from an earlier call
+  #[must_use]
+  pub fn vars(&self) -> Vec<(&'a str, String)> {
+    self
+      .var_names
+      .iter()
+      .enumerate()
+      .filter_map(|(i, name)| match self.env.get(&i) {
+        Value::Uninitialized => None,
+        v => Some((name.as_str(), v.to_string())),
+      })
+      .collect()
+  }
+
+  /// Each call on the stack, innermost first, as its function and the position of the instruction that it is executing. The first entry is the same as ```func``` and ```pos```
+  #[must_use]
+  pub fn backtrace(&self) -> Vec<(&'a str, Option<Position>)> {
+    self.frames.iter().rev().copied().collect()
+  }
+}
+
+// What is handed to a breakpoint callback at the instruction at `pos` in `func`
+fn breakpoint<'b>(
+  env: &'b Environment,
+  frames: &'b [(&'b str, Option<Position>)],
+  func: &'b BBFunction,
+  pos: Option<Position>,
+) -> Breakpoint<'b> {
+  Breakpoint {
+    func: &func.name,
+    pos,
+    env,
+    var_names: &func.var_names,
+    frames,
+  }
+}
+
+/// What the interpreter should do once a breakpoint callback returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+  /// Continue executing
+  Continue,
+  /// Stop executing with an error
+  Abort,
+}
+
+/// Configuration for the optional behaviours of the interpreter. The [`Default`] is the same as running `brili` without any flags.
+#[derive(Debug, Clone, Default)]
+pub struct InterpOptions {
+  /// Output the total number of dynamic instructions to `profiling_out` at the end of execution
+  pub profiling: bool,
+  /// Only count the instructions executed in the function of this name for ```profiling```. The count for a checkpoint is still the total of all instructions
+  pub profile_function: Option<String>,
+  /// Also count the instructions executed by any function that ```profile_function``` calls, directly or indirectly, instead of only the function itself
+  pub profile_callees: bool,
+  /// With ```profiling```, also output the number of `id` instructions executed whose destination is the same variable as their argument, i.e. `self_ids: 2`. These do nothing and usually come from naive code generation. Unlike ```profile_function```, this counts them in every function
+  pub self_ids: bool,
+  /// Output the parsed arguments of "main" along with their types to `profiling_out` before execution, i.e. `input: a:int=5 b:bool=true`
+  pub echo_args: bool,
+  /// The behaviour of integer operations on overflow
+  pub arithmetic: ArithmeticMode,
+  /// The behaviour on overflow of particular operations, i.e. `add`, `sub`, `mul`, `gcd` and `lcm`, which takes precedence over ```arithmetic``` for those operations. Any other operation in the map is ignored
+  ///
+  /// ```
+  /// use brilirs::{basic_block::BBProgram, interp};
+  /// use bril_rs::{Program, ValueOps};
+  ///
+  /// let src = "@main {\n  max: int = const 9223372036854775807;\n  one: int = const 1;\n  two: int = const 2;\n  a: int = add max one;\n  print a;\n  b: int = mul max two;\n  print b;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  /// let options = interp::InterpOptions {
+  ///   arithmetic: interp::ArithmeticMode::Wrapping,
+  ///   arithmetic_overrides: [(ValueOps::Mul, interp::ArithmeticMode::Checked)].into_iter().collect(),
+  ///   ..interp::InterpOptions::default()
+  /// };
+  ///
+  /// let mut out = Vec::new();
+  /// let e = interp::execute_main_with(&bbprog, &mut out, &[], std::io::sink(), &options).unwrap_err();
+  /// // The add wrapped around and the mul was stopped
+  /// assert_eq!(out, b"-9223372036854775808\n");
+  /// assert_eq!(
+  ///   e.to_string(),
+  ///   "Integer overflow in `mul` with arguments `9223372036854775807` and `2`"
+  /// );
+  /// ```
+  pub arithmetic_overrides: FxHashMap<bril_rs::ValueOps, ArithmeticMode>,
+  /// The rounding mode of float arithmetic
+  pub rounding: RoundingMode,
+  /// How `div` rounds its result
+  pub division: DivisionMode,
+  /// How comparisons handle arguments of the wrong type
+  ///
+  /// ```
+  /// use brilirs::{basic_block::BBProgram, interp};
+  /// use bril_rs::Program;
+  ///
+  /// let src = "@main {\n  a: int = const 1;\n  b: float = const 1.0;\n  c: bool = eq a b;\n  print c;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  ///
+  /// let run = |comparison| {
+  ///   let mut out = Vec::new();
+  ///   let options = interp::InterpOptions { comparison, ..interp::InterpOptions::default() };
+  ///   interp::execute_main_with(&bbprog, &mut out, &[], std::io::sink(), &options)
+  ///     .map(|()| out)
+  ///     .map_err(|e| e.to_string())
+  /// };
+  /// assert_eq!(
+  ///   run(interp::ComparisonPolicy::Strict).unwrap_err(),
+  ///   "`eq` can not compare a `int` with a `float`"
+  /// );
+  /// assert_eq!(run(interp::ComparisonPolicy::Numeric).unwrap(), b"true\n");
+  /// ```
+  pub comparison: ComparisonPolicy,
+  /// How an argument of "main" that is too large for an `int` is handled. Literals in the program are handled when it is converted with [`BBProgram::with_int_literals`]
+  pub int_literals: IntLiteralPolicy,
+  /// Output the return value of every call that produces a value to `profiling_out` at the end of execution, in the order that the calls were made, i.e. `call @fib returned 1`. Calls that were resumed from a [`Checkpoint`] are not included
+  pub capture_returns: bool,
+  /// Output the number of instructions executed under each call stack to `profiling_out` at the end of execution as folded stacks, i.e. `main;foo;bar 12`, which can be read by flamegraph.pl
+  pub flamegraph: bool,
+  /// Instead of counting every instruction for ```flamegraph```, sample the call stack once every this many instructions so that each line is the number of samples taken under that stack. Samples are taken at the start of each basic block so a block that crosses more than one multiple of the interval counts for each of them
+  pub flamegraph_interval: Option<std::num::NonZeroU64>,
+  /// Output the largest number of distinct variables written during a single call of each function to `profiling_out` at the end of execution, i.e. `max_live_slots @main: 4`
+  ///
+  /// This approximates register pressure without liveness information. A variable counts from the first time it is written in a call, including as an argument, until the call returns, even if it is never read again. This over-approximates the number of variables that are actually live at once. Calls that do not return, because of an error or a checkpoint, are not counted and calls resumed from a [`Checkpoint`] only count their arguments and the variables written after resuming
+  pub live_slots: bool,
+  /// Check that every printed value parses back into the same value and output a warning to `profiling_out` for each one that does not
+  pub roundtrip_check: bool,
+  /// Have `print` output booleans as `1` and `0` instead of `true` and `false`, for comparing against tools that print them as numbers. Since these do not parse back into booleans, ```roundtrip_check``` warns about them
+  pub numeric_bools: bool,
+  /// Prefix each line that `print` outputs with the number of instructions that have been executed so far, i.e. `[inst 42] 7`, to see when in the run each line was output. Like `clock`, instructions are counted a whole basic block at a time when it is entered, so this is the count at the end of the block that the `print` is in, and every `print` in a block has the same count. The prefix is part of the line that is given to [`execute_main_transforming_prints`], so dropping the line drops the prefix too
+  ///
+  /// ```
+  /// use brilirs::{basic_block::BBProgram, interp};
+  /// use bril_rs::Program;
+  ///
+  /// let src = "@main {\n  a: int = const 1;\n  print a;\n  b: int = const 2;\n  print b;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  /// let options = interp::InterpOptions {
+  ///   print_inst_count: true,
+  ///   ..Default::default()
+  /// };
+  ///
+  /// let mut seen = Vec::new();
+  /// let mut out = Vec::new();
+  /// interp::execute_main_transforming_prints(&bbprog, &mut out, &[], std::io::sink(), &options, &mut |line: &str| {
+  ///   seen.push(line.to_string());
+  ///   // Drop the line that prints 1
+  ///   if line.ends_with(" 1\n") { String::new() } else { line.to_string() }
+  /// })
+  /// .unwrap();
+  ///
+  /// assert_eq!(seen, ["[inst 4] 1\n", "[inst 4] 2\n"]);
+  /// assert_eq!(out, b"[inst 4] 2\n");
+  /// ```
+  pub print_inst_count: bool,
+  /// Report an error when `alloc`, `dup` or `memdup` tries to create an allocation of more than this many cells, before any memory is allocated
+  pub max_alloc_size: Option<u64>,
+  /// Report an error once execution has moved from one basic block to another more than this many times. This catches tight infinite loops sooner than counting instructions would
+  pub max_block_transitions: Option<u64>,
+  /// Track which values are derived from the arguments of "main" and report an error when a tainted pointer is used by `load` or `store`. The rules are described in the README. Taint is not saved in a [`Checkpoint`]
+  pub taint: bool,
+  /// Output each assignment whose value was never read to `profiling_out` at the end of execution, i.e. `dead store: `x` in @main at line 3, column 3`
+  ///
+  /// This is found at runtime rather than with a dataflow analysis. An assignment is only reported if none of the values that it wrote during the whole execution were read by another instruction, so one that is dead on some paths but not others is not reported. Assignments that are never executed are not reported either. Calls resumed from a [`Checkpoint`] are not tracked
+  pub dead_stores: bool,
+  /// Output each variable that held values of more than one type during execution to `profiling_out` at the end of execution, along with those types, i.e. `polymorphic `x` in @main: float, int`. Variables are keyed by their function and name, and pointers count as one type whatever they point to
+  ///
+  /// A program that passes ```check::type_check``` never has such a variable, so this is meant for programs that are run without being checked. Calls resumed from a [`Checkpoint`] only record the variables written after resuming
+  ///
+  /// ```
+  /// use brilirs::{basic_block::BBProgram, interp};
+  /// use bril_rs::Program;
+  ///
+  /// let src = "@main {\n  x: int = const 1;\n  y: int = const 2;\n  x: float = const 2.5;\n  print x y;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  ///
+  /// let mut report = Vec::new();
+  /// let options = interp::InterpOptions {
+  ///   dynamic_types: true,
+  ///   ..Default::default()
+  /// };
+  /// interp::execute_main_with(&bbprog, std::io::sink(), &[], &mut report, &options).unwrap();
+  /// assert_eq!(report, b"polymorphic `x` in @main: float, int\n");
+  /// ```
+  pub dynamic_types: bool,
+  /// Output the number of allocations made by each `alloc`, `dup` and `memdup` instruction to `profiling_out` at the end of execution along with how many of them were freed and leaked, i.e. `alloc in @main at line 3, column 3: 4 allocated, 3 freed, 1 leaked`. Instructions are listed in the order that they were first executed. This is output before a leak is reported as an error so that it shows which instruction made the leaked allocations. Allocations made before resuming from a [`Checkpoint`] are not counted
+  pub alloc_sites: bool,
+  /// Output which lines of the source were executed to `profiling_out` at the end of execution as a single LCOV record, with this as the name of the source file, so that coverage tools such as `genhtml` can report on Bril programs
+  ///
+  /// A line is any line with an instruction on it, which is counted as executed as many times as the most executed instruction on it. Each function is listed by the line that it starts on with the number of times that it was called. Lines and functions are only known when the program was parsed with positions. Instructions are counted a whole basic block at a time when it is entered, so an error in the middle of a block counts the rest of it as executed. Calls resumed from a [`Checkpoint`] do not count the rest of the block that they were resumed in
+  ///
+  /// ```
+  /// use brilirs::{basic_block::BBProgram, interp};
+  /// use bril_rs::Program;
+  ///
+  /// let src = "# The sign of n\n@main(n: int) {\n  zero: int = const 0;\n  neg: bool = lt n zero;\n  br neg .neg .pos;\n.neg:\n  print zero;\n.pos:\n  print n;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  /// let options = interp::InterpOptions {
+  ///   lcov: Some("sign.bril".to_string()),
+  ///   ..Default::default()
+  /// };
+  ///
+  /// let mut lcov = Vec::new();
+  /// interp::execute_main_with(&bbprog, std::io::sink(), &["5".to_string()], &mut lcov, &options).unwrap();
+  /// assert_eq!(
+  ///   String::from_utf8(lcov).unwrap(),
+  ///   "TN:\nSF:sign.bril\nFN:2,main\nFNDA:1,main\nFNF:1\nFNH:1\nDA:3,1\nDA:4,1\nDA:5,1\nDA:7,0\nDA:9,1\nLF:5\nLH:4\nend_of_record\n"
+  /// );
+  /// ```
+  pub lcov: Option<String>,
+  /// When a `load` reads uninitialized memory, output every cell of the allocation to `profiling_out` to show which ones have been initialized
+  pub dump_uninit_reads: bool,
+  /// Output a trace of every call to `profiling_out` at the end of execution in the JSON format of chrome://tracing and Perfetto. Each call is a begin and an end event whose timestamps are the number of instructions executed so far, since blocks are counted when they are entered an end event also includes the rest of the block that made the call. Calls resumed from a [`Checkpoint`] only have an end event
+  pub chrome_trace: bool,
+  /// Output the number of loads of a cell that had already been loaded with no store to it in between to `profiling_out` at the end of execution, i.e. `redundant_loads: 9`. These are the loads that could have reused an earlier result. This is approximate since it ignores whether the earlier result was still held in a variable. Calls resumed from a [`Checkpoint`] forget which cells were loaded before the checkpoint
+  ///
+  /// Each cell, given by the base and offset of a pointer to it, is counted in epochs. An epoch of a cell starts with a `load` of it and lasts until the next `store` to it, from anywhere in the program, which ends the epoch since the value may have changed. Every `load` in an epoch after the one that started it is redundant. A `load` of a cell that was just stored to starts an epoch and is not redundant: every cell has to be stored to before it can be loaded, so counting these would count nearly every `load`. ```store_forwardable_loads``` counts those separately
+  pub redundant_loads: bool,
+  /// Output the number of loads of a cell whose last access was a `store` to it to `profiling_out` at the end of execution, i.e. `store_forwardable_loads: 4`. These are the loads that could have reused the value that was stored. Only the first `load` after a `store` is counted here, since the ones after it are counted by ```redundant_loads```, so the two counts together are the loads of a cell that has not changed since it was last loaded or stored. Calls resumed from a [`Checkpoint`] forget which cells were stored to before the checkpoint
+  pub store_forwardable_loads: bool,
+  /// Require `free` to release the most recent allocation that is still live, as with a stack or arena allocator, and report any other order as an error. Allocations made by `dup` and `memdup` count as well. This is not checked when resuming from a [`Checkpoint`]
+  pub lifo_free: bool,
+  /// Give each allocation the type of the first value stored to it and report an error when a value of another type is stored to it later. Pointers count as one type whatever they point to, and an allocation made by `dup` or `memdup` has the type of the one that it copied. This catches programs that treat an allocation as more than one type, which ```check::type_check``` already rules out, so it is for programs that are run without being checked. This is not checked when resuming from a [`Checkpoint`]
+  ///
+  /// ```
+  /// use brilirs::{basic_block::BBProgram, interp};
+  /// use bril_rs::Program;
+  ///
+  /// let src = "@main {\n  two: int = const 2;\n  p: ptr<int> = alloc two;\n  q: ptr<bool> = id p;\n  store p two;\n  t: bool = const true;\n  store q t;\n  free p;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  ///
+  /// let run = |typed_heap| {
+  ///   let options = interp::InterpOptions { typed_heap, ..Default::default() };
+  ///   interp::execute_main_with(&bbprog, std::io::sink(), &[], std::io::sink(), &options)
+  /// };
+  /// assert!(run(false).is_ok());
+  /// assert_eq!(
+  ///   run(true).unwrap_err().to_string(),
+  ///   "Line 7, Column 3: Stored a `bool` into the allocation with base `0`, which holds `int`"
+  /// );
+  /// ```
+  pub typed_heap: bool,
+  /// When execution stops with an error, output the variables of the call that failed and every allocation on the heap to `profiling_out` along with the position of the error. Variables are sorted by name and allocations by base so that the output is deterministic
+  pub dump_on_error: bool,
+  /// Output a line to `profiling_out` for every memory operation as soon as it succeeds, i.e. `alloc base=3 size=10 at line 4, column 3`. `load` and `store` show the location that they accessed, `free` shows the base that was freed, and `dup` shows the new allocation along with the location that it was copied from
+  pub trace_memory: bool,
+  /// Output a line to `profiling_out` for every heap cell that is read or written, as soon as the instruction succeeds, as a trace for a cache simulator. Each line is `R` for a read or `W` for a write, followed by the base of the allocation and the offset of the cell, i.e. `R 2 5`. `load` reads a cell, `store` writes one, and `dup` reads each cell that it copies and then writes it to the new allocation. Bases are numbered from 0 in the order that allocations are made so the trace is the same on every run
+  pub access_log: bool,
+  /// Output a line to `profiling_out` each time execution enters a basic block, giving the sequence of blocks that were visited, i.e. `block @main 2 .then`. Blocks are numbered from 0 in the order they appear in their function and labelled blocks also show their label. A call shows the blocks of the callee, and the block of the caller is not repeated when the call returns
+  pub trace_blocks: bool,
+  /// Output a line to `profiling_out` for every instruction as soon as it succeeds, giving its function, the instruction, and the value that it assigned if it has a destination, i.e. `@main: c: int = add a b; => 8`. A call is shown once it returns, after the instructions of the callee. This is the trace that ```harness::run_expect_trace``` compares
+  pub trace_instructions: bool,
+  /// Convert an `int` passed to a function argument of type `float` into a `float`. No other conversions are made. The program needs to be checked with ```check::type_check_relaxed_calls``` since this would otherwise be a type error
+  pub relaxed_calls: bool,
+  /// Report an error as soon as `ptradd` creates a pointer with a negative offset instead of when it is used
+  pub strict_pointer_offsets: bool,
+  /// Report an error as soon as `ptradd` creates a pointer more than one past the end of its allocation
+  ///
+  /// As in C, a pointer one past the end of an allocation can always be created, i.e. as the end of a loop over the allocation, but `load` and `store` only accept pointers to the cells inside of the allocation. Without this option, any other offset can be created as well and is only reported when it is used. Together with ```strict_pointer_offsets```, every pointer that is created is between the start and one past the end of its allocation
+  pub strict_pointer_bounds: bool,
+  /// How the result of `undef` behaves. By default it is the zero value of its type, i.e. `0`, `false` or `0.0`, and a pointer which does not point into any allocation. With this option, it is left uninitialized and any instruction other than `phi` which uses it reports an error
+  pub strict_undef: bool,
+  /// Report an error for every use of a value that was never defined, wherever it came from: a variable that has not been assigned on the path that was taken, a heap cell that has not been stored to, or the result of `undef` as with ```strict_undef```. Without this option, a variable that is used before it is assigned makes the interpreter panic. The error names the variable or the heap location that was read
+  pub strict_uninit: bool,
+  /// Make the variables of a call uninitialized when it returns and report an error for every use of an uninitialized variable as with ```strict_uninit```. This is for debugging the interpreter itself: a bug in numbering the variables of a function can make it read the variables of a call that has already returned, which would otherwise quietly give whatever that call left behind. Every call is slower by the number of variables of the function
+  ///
+  /// ```
+  /// use brilirs::{basic_block::BBProgram, interp};
+  /// use bril_rs::Program;
+  ///
+  /// let src = "@f: int {\n  x: int = const 42;\n  ret x;\n}\n@main {\n  a: int = call @f;\n  print a;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let mut bbprog: BBProgram = prog.try_into().unwrap();
+  /// // Number the `a` of `print a` as if it were one past the end of the frame of "main", where the frame of @f was
+  /// let main = &mut bbprog.func_index[1];
+  /// assert_eq!(main.num_of_vars, 1);
+  /// main.blocks[0].numified_instrs[1].args[0] = 1;
+  ///
+  /// let run = |options: &interp::InterpOptions| {
+  ///   let mut out = Vec::new();
+  ///   interp::execute_main_with(&bbprog, &mut out, &[], std::io::sink(), options)
+  ///     .map(|_| String::from_utf8(out).unwrap())
+  ///     .map_err(|e| e.to_string())
+  /// };
+  /// // The value that @f left behind
+  /// assert_eq!(run(&Default::default()).unwrap(), "42\n");
+  /// let options = interp::InterpOptions {
+  ///   poison_frames: true,
+  ///   ..Default::default()
+  /// };
+  /// assert_eq!(
+  ///   run(&options).unwrap_err(),
+  ///   "Line 7, Column 3: `#1` was used while it holds an undefined value"
+  /// );
+  /// ```
+  pub poison_frames: bool,
+  /// Output where each variable printed by `print` was last assigned to `profiling_out` as it is printed, i.e. `print `c` at line 6, column 3: assigned at line 5, column 3`, or `argument of @f` for an argument that has not been assigned since the call started. Calls resumed from a [`Checkpoint`] treat all of their variables as arguments
+  pub print_provenance: bool,
+  /// Output the metrics that are enabled in the other options to `profiling_out` as a single line JSON object at the end of execution instead of their text output, for tools that collect them
+  ///
+  /// The object always has `version`, which is bumped whenever the meaning of a field changes, and `leaked_bases`, the bases of the allocations that were never freed. It is written before checking for leaks, so it is there even when the run then fails with a leak. Every other field is only there if its option is enabled: `total_dyn_inst`, `self_ids` and `wraparounds` with ```profiling```, and `call_returns`, `dead_stores`, `redundant_loads`, `store_forwardable_loads`, `polymorphic`, `max_live_slots` and `alloc_sites` with the option of the same name. Fields are always in that order and lists are in the order of the text output. The flamegraph and Chrome trace are their own formats and are still written after the object
+  ///
+  /// ```
+  /// use brilirs::{basic_block::BBProgram, interp};
+  /// use bril_rs::Program;
+  ///
+  /// let src = "@main {\n  n: int = const 2;\n  x: int = call @double n;\n  y: int = const 1;\n}\n@double(n: int): int {\n  r: int = add n n;\n  ret r;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  /// let options = interp::InterpOptions {
+  ///   json_summary: true,
+  ///   profiling: true,
+  ///   capture_returns: true,
+  ///   dead_stores: true,
+  ///   ..interp::InterpOptions::default()
+  /// };
+  ///
+  /// let mut summary = Vec::new();
+  /// interp::execute_main_with(&bbprog, std::io::sink(), &[], &mut summary, &options).unwrap();
+  /// assert_eq!(
+  ///   String::from_utf8(summary).unwrap(),
+  ///   concat!(
+  ///     r#"{"version":1,"total_dyn_inst":5,"call_returns":[{"function":"double","value":"4"}],"#,
+  ///     r#""dead_stores":[{"function":"main","variable":"x","line":3,"column":3},"#,
+  ///     r#"{"function":"main","variable":"y","line":4,"column":3}],"leaked_bases":[]}"#,
+  ///     "\n"
+  ///   )
+  /// );
+  /// ```
+  pub json_summary: bool,
+  /// Output a warning to `profiling_out` when `free` is given an allocation that was returned by a call while a variable other than the one given to `free`, a caller, or another allocation still points into it, i.e. `warning: `free` at line 9, column 3 frees the allocation returned by @make while it is still reachable from `alias``. Any later use of what is left pointing into the allocation is a use after free. Only variables of the current call are named
+  pub dangling_returns: bool,
+  /// Limit how fast `print` outputs lines
+  pub print_throttle: Option<PrintThrottle>,
+  /// Output a warning to `profiling_out` for every `add`, `sub` or `mul` that wraps around under [`ArithmeticMode::Wrapping`], i.e. `warning: `add` at line 4, column 3 wrapped around: 9223372036854775807 and 1 gave -9223372036854775808`. The result is not changed. With ```profiling```, also output the number of them, i.e. `wraparounds: 1`. This is for finding accidental overflows in programs that were not meant to depend on wrapping
+  pub wraparound_warnings: bool,
+  /// Flush `out` after every this many lines that `print` outputs instead of after each one, which is faster when `out` is buffered but lets the output fall behind the rest of what the program does, such as the warnings written to `profiling_out`. `out` is always flushed when execution stops, even with an error. [`None`] is the same as 1
+  ///
+  /// ```
+  /// use brilirs::{basic_block::BBProgram, interp};
+  /// use bril_rs::Program;
+  ///
+  /// // Only keeps what has been flushed
+  /// #[derive(Default)]
+  /// struct Flushed {
+  ///   pending: Vec<u8>,
+  ///   flushed: Vec<u8>,
+  ///   flushes: usize,
+  /// }
+  /// impl std::io::Write for Flushed {
+  ///   fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+  ///     self.pending.extend(buf);
+  ///     Ok(buf.len())
+  ///   }
+  ///   fn flush(&mut self) -> std::io::Result<()> {
+  ///     self.flushed.append(&mut self.pending);
+  ///     self.flushes += 1;
+  ///     Ok(())
+  ///   }
+  /// }
+  ///
+  /// let src = "@main {\n  a: int = const 1;\n  print a;\n  print a;\n  print a;\n  print a;\n  print a;\n}";
+  /// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+  ///   .try_into()
+  ///   .unwrap();
+  /// let bbprog: BBProgram = prog.try_into().unwrap();
+  /// let options = interp::InterpOptions {
+  ///   flush_every: Some(3.try_into().unwrap()),
+  ///   ..Default::default()
+  /// };
+  ///
+  /// let mut out = Flushed::default();
+  /// interp::execute_main_with(&bbprog, &mut out, &[], std::io::sink(), &options).unwrap();
+  /// // Once after the third line and once at the end
+  /// assert_eq!(out.flushes, 2);
+  /// assert_eq!(out.flushed, b"1\n1\n1\n1\n1\n");
+  /// ```
+  pub flush_every: Option<std::num::NonZeroU32>,
+  /// Output the number of allocations made by `alloc` and `alloca` of each size to `profiling_out` at the end of execution, in order of size, i.e. `alloc_size 16: 5`. Allocations made by `dup` and `memdup` are not counted, and neither are those made after resuming from a [`Checkpoint`]
+  pub alloc_sizes: bool,
+  /// The format of the output of ```profiling```
+  pub profile_format: ProfileFormat,
+  /// When `print` flushes `out`
+  pub output_buffering: OutputBuffering,
+  /// Report an error when a pure function is called with the same arguments as a call of it that has not returned yet. Such a call is certain to do the same and so on, so the program would never stop. A function is pure when it does nothing but compute a value from its arguments: it does not use memory, `print`, `clock`, `argc`, `unique_id`, `dump`, `debugbreak` or speculation, and only calls pure functions. Other functions are not checked since their calls can depend on more than their arguments. Calls resumed from a [`Checkpoint`] are not checked, and neither are calls replaced by stubs
+  pub detect_infinite_recursion: bool,
+  /// Report an error when `store` writes a pointer to an allocation made by `alloca` into an allocation that outlives it, which is any allocation made by `alloc`, `dup` or `memdup` or by `alloca` in a caller. Such a pointer would be left dangling once the call that made the allocation returns. Pointers that escape in other ways, such as by being returned or copied by `dup` or `memdup`, are not caught. This is not checked for allocations made before resuming from a [`Checkpoint`]
+  pub check_escapes: bool,
+}
+
+impl InterpOptions {
+  // The behaviour of `op` on overflow, which is its override if it has one
+  #[inline(always)]
+  fn arithmetic_for(&self, op: bril_rs::ValueOps) -> ArithmeticMode {
+    if self.arithmetic_overrides.is_empty() {
+      self.arithmetic
+    } else {
+      self
+        .arithmetic_overrides
+        .get(&op)
+        .copied()
+        .unwrap_or(self.arithmetic)
+    }
+  }
+}
+
+/// The entrance point to the interpreter. It runs over a ```prog```:[`BBProgram`] starting at the "main" function with ```input_args``` as input. Print statements output to ```out``` which implements [std::io::Write]. You also need to include whether you want the interpreter to count the number of instructions run with ```profiling```. This information is outputted to [std::io::stderr]
+pub fn execute_main<T: std::io::Write, U: std::io::Write>(
+  prog: &BBProgram,
+  out: T,
+  input_args: &[String],
+  profiling: bool,
+  profiling_out: U,
+) -> Result<(), PositionalInterpError> {
+  execute_main_with(
+    prog,
+    out,
+    input_args,
+    profiling_out,
+    &InterpOptions {
+      profiling,
+      ..InterpOptions::default()
+    },
+  )
+}
+
+/// The same as [`execute_main`] except that the behaviour of the interpreter is configured through ```options```
+///
+/// Any diagnostics requested in ```options``` are outputted to ```profiling_out```. They are always written in the same order so two runs of the same program produce exactly the same bytes.
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp};
+/// use bril_rs::Program;
+///
+/// let src = "@main { n: int = const 3; a: int = call @f n; b: int = call @g n; print a b; }
+///   @f(n: int): int { r: int = call @g n; ret r; }
+///   @g(n: int): int { one: int = const 1; r: int = add n one; ret r; }";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+/// let options = interp::InterpOptions {
+///   profiling: true,
+///   capture_returns: true,
+///   flamegraph: true,
+///   live_slots: true,
+///   ..interp::InterpOptions::default()
+/// };
+///
+/// let run = || {
+///   let mut diagnostics = Vec::new();
+///   interp::execute_main_with(&bbprog, std::io::sink(), &[], &mut diagnostics, &options).unwrap();
+///   diagnostics
+/// };
+/// assert_eq!(run(), run());
+/// ```
+pub fn execute_main_with<T: std::io::Write, U: std::io::Write>(
+  prog: &BBProgram,
+  out: T,
+  input_args: &[String],
+  profiling_out: U,
+  options: &InterpOptions,
+) -> Result<(), PositionalInterpError> {
+  execute_main_until(prog, out, input_args, profiling_out, options, None).map(|_| ())
+}
+
+/// The same as [`execute_main_with`] except that when ```checkpoint_at``` is provided, execution is suspended at the first basic block boundary after that many dynamic instructions have been executed. The [`Checkpoint`] of the suspended execution is returned, or `None` if "main" finished first.
+pub fn execute_main_until<T: std::io::Write, U: std::io::Write>(
+  prog: &BBProgram,
+  out: T,
+  input_args: &[String],
+  profiling_out: U,
+  options: &InterpOptions,
+  checkpoint_at: Option<u32>,
+) -> Result<Option<Checkpoint>, PositionalInterpError> {
+  run_main(
+    prog,
+    out,
+    input_args,
+    profiling_out,
+    options,
+    checkpoint_at,
+    Hooks::default(),
+  )
+  .map(|(checkpoint, _)| checkpoint)
+}
+
+/// The same as [`execute_main_with`] except that ```on_break``` is called whenever a `debugbreak` is executed
+///
+/// Execution is paused until ```on_break``` returns and stops with an error if it returns [`Resume::Abort`].
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp};
+/// use bril_rs::Program;
+///
+/// let src = "@main {\n  a: int = const 4;\n  debugbreak;\n  print a;\n}";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+///
+/// let mut hits = Vec::new();
+/// let mut out = Vec::new();
+/// interp::execute_main_with_breakpoints(
+///   &bbprog,
+///   &mut out,
+///   &[],
+///   std::io::sink(),
+///   &interp::InterpOptions::default(),
+///   &mut |b: &interp::Breakpoint| {
+///     let pos = b.pos.unwrap();
+///     hits.push((b.func.to_string(), pos.row, pos.col, b.vars().into_iter().map(|(n, v)| format!("{n}={v}")).collect::<Vec<_>>()));
+///     interp::Resume::Continue
+///   },
+/// )
+/// .unwrap();
+///
+/// assert_eq!(hits, vec![("main".to_string(), 3, 3, vec!["a=4".to_string()])]);
+/// assert_eq!(out, b"4\n");
+/// ```
+pub fn execute_main_with_breakpoints<T: std::io::Write, U: std::io::Write>(
+  prog: &BBProgram,
+  out: T,
+  input_args: &[String],
+  profiling_out: U,
+  options: &InterpOptions,
+  on_break: &mut dyn FnMut(&Breakpoint) -> Resume,
+) -> Result<(), PositionalInterpError> {
+  execute_main_with_hooks(
+    prog,
+    out,
+    input_args,
+    profiling_out,
+    options,
+    Hooks {
+      on_break: Some(on_break),
+      ..Hooks::default()
+    },
+  )
+}
+
+/// The same as [`execute_main_with`] except that ```on_step``` is called before every instruction is executed
+///
+/// This is the building block for a debugger that single steps through a program. Execution stops with an error if ```on_step``` returns [`Resume::Abort`].
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp};
+/// use bril_rs::Program;
+///
+/// let src = "@main {\n  a: int = const 4;\n  b: int = call @double a;\n  print b;\n}\n@double(x: int): int {\n  y: int = add x x;\n  ret y;\n}";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+///
+/// let mut steps = Vec::new();
+/// interp::execute_main_stepping(
+///   &bbprog,
+///   std::io::sink(),
+///   &[],
+///   std::io::sink(),
+///   &interp::InterpOptions::default(),
+///   &mut |b: &interp::Breakpoint| {
+///     let calls: Vec<_> = b.backtrace().iter().map(|(f, pos)| format!("{f}:{}", pos.unwrap().row)).collect();
+///     steps.push(calls.join(" < "));
+///     interp::Resume::Continue
+///   },
+/// )
+/// .unwrap();
+///
+/// assert_eq!(steps, ["main:2", "main:3", "double:7 < main:3", "double:8 < main:3", "main:4"]);
+/// ```
+pub fn execute_main_stepping<T: std::io::Write, U: std::io::Write>(
+  prog: &BBProgram,
+  out: T,
+  input_args: &[String],
+  profiling_out: U,
+  options: &InterpOptions,
+  on_step: &mut dyn FnMut(&Breakpoint) -> Resume,
+) -> Result<(), PositionalInterpError> {
+  execute_main_with_hooks(
+    prog,
+    out,
+    input_args,
+    profiling_out,
+    options,
+    Hooks {
+      on_step: Some(on_step),
+      ..Hooks::default()
+    },
+  )
+}
+
+/// The same as [`execute_main_with`] except that each line output by `print` is passed to ```on_print``` and what it returns is written to ```out``` instead
+///
+/// ```on_print``` is given exactly what would have been written, including the newline at the end, so returning it unchanged is the same as not having a transformer and returning an empty string drops the line. The line is transformed when it is printed even if it is held back by a speculation that is later aborted.
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp};
+/// use bril_rs::Program;
+///
+/// let src = "@main {\n  n: int = const 7;\n  x: float = const 0.1;\n  print n x;\n  t: bool = const true;\n  print t;\n}";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+///
+/// let mut seen = Vec::new();
+/// let mut out = Vec::new();
+/// interp::execute_main_transforming_prints(
+///   &bbprog,
+///   &mut out,
+///   &[],
+///   std::io::sink(),
+///   &interp::InterpOptions::default(),
+///   &mut |line: &str| {
+///     seen.push(line.to_string());
+///     // Redact the first line and drop the second
+///     if line.starts_with('7') { "redacted\n".to_string() } else { String::new() }
+///   },
+/// )
+/// .unwrap();
+///
+/// assert_eq!(seen, ["7 0.1\n", "true\n"]);
+/// assert_eq!(out, b"redacted\n");
+/// ```
+pub fn execute_main_transforming_prints<T: std::io::Write, U: std::io::Write>(
+  prog: &BBProgram,
+  out: T,
+  input_args: &[String],
+  profiling_out: U,
+  options: &InterpOptions,
+  on_print: &mut dyn FnMut(&str) -> String,
+) -> Result<(), PositionalInterpError> {
+  execute_main_with_hooks(
+    prog,
+    out,
+    input_args,
+    profiling_out,
+    options,
+    Hooks {
+      on_print: Some(on_print),
+      ..Hooks::default()
+    },
+  )
+}
+
+/// The same as [`execute_main_with`] except that the arithmetic and comparisons are done by ```semantics```
+///
+/// See [`Semantics`] for which operations are covered.
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp, semantics::{ConcreteSemantics, Semantics}};
+/// use bril_rs::Program;
+///
+/// // Counts the additions and comparisons while keeping their concrete results
+/// #[derive(Default)]
+/// struct Counting {
+///   adds: u32,
+///   compares: u32,
+/// }
+///
+/// impl Semantics for Counting {
+///   fn add(&mut self, a: i64, b: i64) -> i64 {
+///     self.adds += 1;
+///     ConcreteSemantics.add(a, b)
+///   }
+///   fn lt(&mut self, a: i64, b: i64) -> bool {
+///     self.compares += 1;
+///     ConcreteSemantics.lt(a, b)
+///   }
+/// }
+///
+/// let src = "@main {\n  i: int = const 0;\n  one: int = const 1;\n  n: int = const 3;\n.loop:\n  c: bool = lt i n;\n  br c .body .done;\n.body:\n  i: int = add i one;\n  jmp .loop;\n.done:\n  print i;\n}";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+///
+/// let mut counting = Counting::default();
+/// let mut out = Vec::new();
+/// interp::execute_main_with_semantics(
+///   &bbprog,
+///   &mut out,
+///   &[],
+///   std::io::sink(),
+///   &interp::InterpOptions::default(),
+///   &mut counting,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(out, b"3\n");
+/// assert_eq!((counting.adds, counting.compares), (3, 4));
+/// ```
+pub fn execute_main_with_semantics<T: std::io::Write, U: std::io::Write>(
+  prog: &BBProgram,
+  out: T,
+  input_args: &[String],
+  profiling_out: U,
+  options: &InterpOptions,
+  semantics: &mut dyn Semantics,
+) -> Result<(), PositionalInterpError> {
+  execute_main_with_hooks(
+    prog,
+    out,
+    input_args,
+    profiling_out,
+    options,
+    Hooks {
+      semantics: Some(semantics),
+      ..Hooks::default()
+    },
+  )
+}
+
+/// The same as [`execute_main_with`] except that calls of functions that ```prog``` does not define are made to ```natives```
+///
+/// ```prog``` should have been converted with [`BBProgram::with_natives`]. See [`NativeFunctions`] for how the values are passed.
+pub fn execute_main_with_natives<T: std::io::Write, U: std::io::Write>(
+  prog: &BBProgram,
+  out: T,
+  input_args: &[String],
+  profiling_out: U,
+  options: &InterpOptions,
+  natives: &NativeFunctions,
+) -> Result<(), PositionalInterpError> {
+  execute_main_with_hooks(
+    prog,
+    out,
+    input_args,
+    profiling_out,
+    options,
+    Hooks {
+      natives: Some(natives),
+      ..Hooks::default()
+    },
+  )
+}
+
+/// What a caller can hook into a run of "main" with [`execute_main_with_hooks`]. Each field is the same as the argument of the function that runs a program with only that hook, and any of them can be combined. The [`Default`] has no hooks, which is the same as [`execute_main_with`]
+#[derive(Default)]
+pub struct Hooks<'a> {
+  /// Called whenever a `debugbreak` is executed, as in [`execute_main_with_breakpoints`]
+  pub on_break: Option<&'a mut dyn FnMut(&Breakpoint) -> Resume>,
+  /// Called before every instruction is executed, as in [`execute_main_stepping`]
+  pub on_step: Option<&'a mut dyn FnMut(&Breakpoint) -> Resume>,
+  /// Replaces the built-in arithmetic and comparisons, as in [`execute_main_with_semantics`]
+  pub semantics: Option<&'a mut dyn Semantics>,
+  /// Transforms each line that `print` outputs, as in [`execute_main_transforming_prints`]
+  pub on_print: Option<&'a mut dyn FnMut(&str) -> String>,
+  /// Called for calls of functions that the program does not define, as in [`execute_main_with_natives`]
+  pub natives: Option<&'a NativeFunctions>,
+  /// Stops the program with an error once it has run for this long, as in [`execute_main_with_timeout`]
+  pub timeout: Option<std::time::Duration>,
+  /// The heap to run the program with, as in [`SharedHeap::run`]
+  pub shared_heap: Option<&'a mut SharedHeap>,
+}
+
+/// The same as [`execute_main_with`] except with the callbacks and other hooks of ```hooks```
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp, native::NativeFunctions, record::FieldValue};
+/// use bril_rs::Program;
+///
+/// let mut natives = NativeFunctions::default();
+/// natives.register("square", |args| match args {
+///   [FieldValue::Int(x)] => FieldValue::Int(x * x),
+///   _ => FieldValue::Int(0),
+/// });
+///
+/// let src = "@main {\n  x: int = const 3;\n  y: int = call @square x;\n  print y;\n}";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+///   .try_into()
+///   .unwrap();
+/// let bbprog = BBProgram::with_natives(prog, &natives).unwrap();
+///
+/// let mut rows = Vec::new();
+/// let mut out = Vec::new();
+/// interp::execute_main_with_hooks(
+///   &bbprog,
+///   &mut out,
+///   &[],
+///   std::io::sink(),
+///   &interp::InterpOptions::default(),
+///   interp::Hooks {
+///     natives: Some(&natives),
+///     on_step: Some(&mut |b: &interp::Breakpoint| {
+///       rows.push(b.pos.unwrap().row);
+///       interp::Resume::Continue
+///     }),
+///     timeout: Some(std::time::Duration::from_secs(10)),
+///     ..interp::Hooks::default()
+///   },
+/// )
+/// .unwrap();
+///
+/// assert_eq!(out, b"9\n");
+/// assert_eq!(rows, [2, 3, 4]);
+/// ```
+pub fn execute_main_with_hooks<'a, T: std::io::Write, U: std::io::Write>(
+  prog: &'a BBProgram,
+  out: T,
+  input_args: &[String],
+  profiling_out: U,
+  options: &'a InterpOptions,
+  hooks: Hooks<'a>,
+) -> Result<(), PositionalInterpError> {
+  run_main(prog, out, input_args, profiling_out, options, None, hooks).map(|_| ())
+}
+
+// Runs "main" and also returns the number of dynamic instructions that were executed
+pub(crate) fn run_main<'a, T: std::io::Write, U: std::io::Write>(
+  prog: &'a BBProgram,
+  out: T,
+  input_args: &[String],
+  profiling_out: U,
+  options: &'a InterpOptions,
+  checkpoint_at: Option<u32>,
+  hooks: Hooks<'a>,
+) -> Result<(Option<Checkpoint>, u32), PositionalInterpError> {
+  let (mut state, main_func) = start_main(prog, out, input_args, profiling_out, options)?;
+  state.checkpoint_at = checkpoint_at;
+  state.on_break = hooks.on_break;
+  state.on_step = hooks.on_step;
+  state.semantics = hooks.semantics;
+  state.on_print = hooks.on_print;
+  state.natives = hooks.natives;
+  if let Some(timeout) = hooks.timeout {
+    state.deadline = Some((std::time::Instant::now() + timeout, timeout));
+  }
+  let mut shared_heap = hooks.shared_heap;
+  if let Some(shared) = shared_heap.as_deref_mut() {
+    state.heap = std::mem::take(&mut shared.heap);
+  }
+
+  let result = run(&mut state, main_func);
+
+  // What is left belongs to the shared heap rather than to this program, so the leak check finds nothing
+  if let Some(shared) = shared_heap {
+    shared.heap = std::mem::take(&mut state.heap);
+  }
+  suspend_or_finish(state, main_func, result)
+}
+
+/// Runs "main" of ```prog``` with ```input_args``` and hashes everything that can be observed about the run
+///
+/// This is what was printed, whether it finished or the error that it stopped with, and the contents of the memory that was not freed. Two runs with the same hash are observably equivalent, barring a collision. This makes it cheap to check that an optimized program behaves the same as the original. The hash is FNV-1a over a stable serialization of the state so it is the same across platforms and versions of Rust. Diagnostics requested in ```options``` are not included.
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp};
+/// use bril_rs::Program;
+///
+/// let parse = |src: &str| -> BBProgram {
+///   let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///     .try_into()
+///     .unwrap();
+///   prog.try_into().unwrap()
+/// };
+/// let options = interp::InterpOptions::default();
+/// let original = parse("@main { a: int = const 2; b: int = const 3; c: int = mul a b; print c; }");
+/// let folded = parse("@main { c: int = const 6; print c; }");
+/// let different = parse("@main { c: int = const 7; print c; }");
+///
+/// let hash = |prog: &BBProgram| interp::final_state_hash(prog, &[], &options);
+/// assert_eq!(hash(&original), hash(&folded));
+/// assert_ne!(hash(&original), hash(&different));
+/// ```
+#[must_use]
+pub fn final_state_hash(prog: &BBProgram, input_args: &[String], options: &InterpOptions) -> u64 {
+  let mut out = Vec::new();
+  let mut heap = Vec::new();
+  let result = start_main(prog, &mut out, input_args, std::io::sink(), options).and_then(
+    |(mut state, main_func)| {
+      let result = run(&mut state, main_func);
+      write_heap(&mut heap, &state.heap.inner).expect("writing to a Vec should not fail");
+      finish_main(&mut state, main_func, result)
+    },
+  );
+
+  let mut bytes = format!("output {}\n", out.len()).into_bytes();
+  bytes.extend_from_slice(&out);
+  match result {
+    Ok(_) => bytes.extend_from_slice(b"\nok\n"),
+    Err(e) => bytes.extend_from_slice(format!("\nerror {e}\n").as_bytes()),
+  }
+  bytes.extend_from_slice(&heap);
+
+  bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+    (hash ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3)
+  })
+}
+
+/// Runs "main" of ```prog``` like [`execute_main`] and returns every allocation that is still live when it finishes, so that a test can check the data structure that a program built
+///
+/// The allocations are keyed by base, in the order that they were made, and hold one entry per cell, which is `None` for a cell that was never stored to. A pointer stored in a cell is given by the base of the allocation it points into and its offset, the same as the keys of the map. The heap is collected before the check for leaks, so a program does not have to free what it built and the check is not done.
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp, record::FieldValue};
+/// use bril_rs::Program;
+///
+/// // A linked list of 1 -> 2 where each node is [value, next]. A node holds both an int and a
+/// // pointer, which runs since nothing here type checks the program
+/// let src = "@main {
+///   two: int = const 2;
+///   one: int = const 1;
+///   second: ptr<int> = alloc two;
+///   first: ptr<int> = alloc two;
+///   store first one;
+///   next: ptr<int> = ptradd first one;
+///   store next second;
+///   store second two;
+/// }";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+///
+/// let heap = interp::execute_main_collecting_heap(
+///   &bbprog,
+///   std::io::sink(),
+///   &[],
+///   std::io::sink(),
+///   &interp::InterpOptions::default(),
+/// )
+/// .unwrap();
+///
+/// assert_eq!(heap.len(), 2);
+/// assert_eq!(
+///   heap[&1],
+///   vec![
+///     Some(FieldValue::Int(1)),
+///     Some(FieldValue::Pointer { base: 0, offset: 0 })
+///   ]
+/// );
+/// // The end of the list was never given a next node
+/// assert_eq!(heap[&0], vec![Some(FieldValue::Int(2)), None]);
+/// ```
+pub fn execute_main_collecting_heap<T: std::io::Write, U: std::io::Write>(
+  prog: &BBProgram,
+  out: T,
+  input_args: &[String],
+  profiling_out: U,
+  options: &InterpOptions,
+) -> Result<BTreeMap<usize, Vec<Option<FieldValue>>>, PositionalInterpError> {
+  let (mut state, main_func) = start_main(prog, out, input_args, profiling_out, options)?;
+  let result = run(&mut state, main_func);
+  // Taking the heap leaves an empty one behind, so there is nothing left for the leak check to find
+  let heap = std::mem::take(&mut state.heap);
+  finish_main(&mut state, main_func, result)?;
+  Ok(
+    heap
+      .inner
+      .memory
+      .iter()
+      .map(|(base, cells)| (*base, cells.iter().map(FieldValue::from_value).collect()))
+      .collect(),
+  )
+}
+
+/// Runs "main" of ```prog``` like [`execute_main_with`], stopping it if it is still running after ```timeout```, and returns what it printed along with how it finished
+///
+/// The output is returned even when the run fails, so that it is possible to see how far a slow program got before it was cut off. A run that ran out of time finishes with an error whose [`ErrorKind`](crate::ErrorKind) is `TimedOut`. The time is checked when moving between basic blocks, so a run can go a little over ```timeout``` before it is stopped.
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp, ErrorKind};
+/// use bril_rs::Program;
+///
+/// let src = "@main {\n  n: int = const 1;\n  print n;\n.loop:\n  jmp .loop;\n.done:\n  print n;\n}";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+///
+/// let (out, result) = interp::execute_main_with_timeout(
+///   &bbprog,
+///   &[],
+///   std::io::sink(),
+///   &interp::InterpOptions::default(),
+///   std::time::Duration::from_millis(10),
+/// );
+/// assert_eq!(out, b"1\n");
+/// assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+/// ```
+pub fn execute_main_with_timeout<U: std::io::Write>(
+  prog: &BBProgram,
+  input_args: &[String],
+  profiling_out: U,
+  options: &InterpOptions,
+  timeout: std::time::Duration,
+) -> (Vec<u8>, Result<(), PositionalInterpError>) {
+  let mut out = Vec::new();
+  let result = execute_main_with_hooks(
+    prog,
+    &mut out,
+    input_args,
+    profiling_out,
+    options,
+    Hooks {
+      timeout: Some(timeout),
+      ..Hooks::default()
+    },
+  );
+  (out, result)
+}
+
+/// A heap that more than one program uses, for simulating processes that communicate through shared memory
+///
+/// Each program is run with [`SharedHeap::run`], which is like [`execute_main_with`] except that the program allocates from, reads and writes, and frees the shared heap. Programs take turns: one runs to completion before the next one starts, in the order that they are run, so there is never more than one of them touching the heap and nothing needs to be synchronized. What a program leaves allocated stays in the shared heap for the programs after it instead of being reported as a leak, and [`SharedHeap::finish`] reports what is left once every program is done.
+///
+/// An allocation made by one program is handed to another through an argument of "main" of type `ptr`, which is given as the base of the allocation, i.e. `0`, and points to its start. Bases are never reused, so the base of an allocation is the same for every program, and [`SharedHeap::bases`] lists the allocations that are still live. The options that change how the heap is checked, such as ```lifo_free``` and ```typed_heap```, are those given to [`SharedHeap::new`].
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp};
+/// use bril_rs::Program;
+///
+/// let parse = |src: &str| -> BBProgram {
+///   let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///     .try_into()
+///     .unwrap();
+///   prog.try_into().unwrap()
+/// };
+/// let writer = parse("@main {\n  one: int = const 1;\n  p: ptr<int> = alloc one;\n  v: int = const 42;\n  store p v;\n}");
+/// let reader = parse("@main(p: ptr<int>) {\n  v: int = load p;\n  print v;\n  free p;\n}");
+/// let options = interp::InterpOptions::default();
+///
+/// let mut heap = interp::SharedHeap::new(&options);
+/// heap.run(&writer, std::io::sink(), &[], std::io::sink(), &options).unwrap();
+/// assert_eq!(heap.bases(), [0]);
+///
+/// let mut out = Vec::new();
+/// heap.run(&reader, &mut out, &["0".to_string()], std::io::sink(), &options).unwrap();
+/// assert_eq!(out, b"42\n");
+/// heap.finish().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct SharedHeap {
+  heap: CheckedHeap,
+}
+
+impl SharedHeap {
+  /// Creates an empty heap that is checked according to ```options```
+  #[must_use]
+  pub fn new(options: &InterpOptions) -> Self {
+    Self {
+      heap: CheckedHeap::new(Heap::default(), options),
+    }
+  }
+
+  /// Runs "main" of ```prog``` to completion using this heap. The allocations that are live when it finishes, even with an error, are left for the next program
+  pub fn run<T: std::io::Write, U: std::io::Write>(
+    &mut self,
+    prog: &BBProgram,
+    out: T,
+    input_args: &[String],
+    profiling_out: U,
+    options: &InterpOptions,
+  ) -> Result<(), PositionalInterpError> {
+    execute_main_with_hooks(
+      prog,
+      out,
+      input_args,
+      profiling_out,
+      options,
+      Hooks {
+        shared_heap: Some(self),
+        ..Hooks::default()
+      },
+    )
+  }
+
+  /// The bases of the allocations that are live, in increasing order
+  #[must_use]
+  pub fn bases(&self) -> Vec<usize> {
+    self.heap.bases()
+  }
+
+  /// Reports an error if any allocation is still live, once every program is done with the heap
+  pub fn finish(self) -> Result<(), PositionalInterpError> {
+    if self.heap.is_empty() {
+      Ok(())
+    } else {
+      Err(PositionalInterpError::new(InterpError::MemLeak))
+    }
+  }
+}
+
+/// Runs "main" of ```prog``` like [`execute_main_with`] with ```heap``` as its heap instead of a [`Heap`]
+///
+/// This is how a [`HeapBackend`] other than [`Heap`] is used. ```heap``` should be empty, as from [`Default`], and is left with whatever the program did not free so that it can be inspected afterwards. The checks that ```options``` turn on, such as ```lifo_free```, ```typed_heap``` and ```alloc_sizes```, are done by the interpreter and apply to any backend.
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp};
+/// use brilirs::interp::{Heap, HeapBackend, HeapError, Pointer, Value};
+/// use bril_rs::Program;
+/// use std::cell::Cell;
+///
+/// // Counts the cells that are read and written by passing everything on to a `Heap`
+/// #[derive(Default)]
+/// struct Counting {
+///   heap: Heap,
+///   reads: Cell<usize>,
+///   writes: usize,
+/// }
+///
+/// impl HeapBackend for Counting {
+///   fn alloc(&mut self, amount: i64, max_size: Option<u64>) -> Result<Value, HeapError> {
+///     self.heap.alloc(amount, max_size)
+///   }
+///   fn dup(&mut self, key: &Pointer, amount: i64, max_size: Option<u64>) -> Result<Value, HeapError> {
+///     self.heap.dup(key, amount, max_size)
+///   }
+///   fn free(&mut self, key: &Pointer) -> Result<(), HeapError> {
+///     self.heap.free(key)
+///   }
+///   fn read(&self, key: &Pointer) -> Result<&Value, HeapError> {
+///     self.reads.set(self.reads.get() + 1);
+///     self.heap.read(key)
+///   }
+///   fn write(&mut self, key: &Pointer, val: Value) -> Result<(), HeapError> {
+///     self.writes += 1;
+///     self.heap.write(key, val)
+///   }
+///   fn is_empty(&self) -> bool {
+///     self.heap.is_empty()
+///   }
+///   fn len(&self) -> usize {
+///     self.heap.len()
+///   }
+///   fn block(&self, base: usize) -> Option<&[Value]> {
+///     self.heap.block(base)
+///   }
+///   fn bases(&self) -> Vec<usize> {
+///     self.heap.bases()
+///   }
+/// }
+///
+/// let parse = |src: &str| -> BBProgram {
+///   let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///     .try_into()
+///     .unwrap();
+///   prog.try_into().unwrap()
+/// };
+/// let prog = parse("@main {\n  two: int = const 2;\n  p: ptr<int> = alloc two;\n  store p two;\n  one: int = const 1;\n  q: ptr<int> = ptradd p one;\n  store q one;\n  v: int = load q;\n  print v;\n  free p;\n}");
+///
+/// let mut heap = Counting::default();
+/// let mut out = Vec::new();
+/// let options = interp::InterpOptions::default();
+/// interp::execute_main_with_heap(&prog, &mut out, &[], std::io::sink(), &options, &mut heap).unwrap();
+/// assert_eq!(out, b"1\n");
+/// assert_eq!((heap.reads.get(), heap.writes), (1, 2));
+/// assert!(heap.is_empty());
+///
+/// // The checks of the options are done whatever the backend is
+/// let fifo = parse("@main {\n  one: int = const 1;\n  p: ptr<int> = alloc one;\n  q: ptr<int> = alloc one;\n  free p;\n  free q;\n}");
+/// let options = interp::InterpOptions { lifo_free: true, ..interp::InterpOptions::default() };
+/// let result = interp::execute_main_with_heap(
+///   &fifo,
+///   std::io::sink(),
+///   &[],
+///   std::io::sink(),
+///   &options,
+///   &mut Counting::default(),
+/// );
+/// assert_eq!(
+///   result.unwrap_err().to_string(),
+///   "Freed the allocation with base `0` before the more recent allocation with base `1`"
+/// );
+/// ```
+pub fn execute_main_with_heap<T: std::io::Write, U: std::io::Write, H: HeapBackend>(
+  prog: &BBProgram,
+  out: T,
+  input_args: &[String],
+  profiling_out: U,
+  options: &InterpOptions,
+  heap: &mut H,
+) -> Result<(), PositionalInterpError> {
+  let main_func = get_main(prog)?;
+  let mut state: State<_, _, H> =
+    start_function(prog, main_func, out, input_args, profiling_out, options)?;
+  state.heap.inner = std::mem::take(heap);
+  let result = run(&mut state, main_func);
+  let result = finish_main(&mut state, main_func, result);
+  *heap = std::mem::take(&mut state.heap.inner);
+  result.map(|_| ())
+}
+
+// Parses the arguments of "main" and sets up the state to execute it
+fn start_main<'a, T: std::io::Write, U: std::io::Write>(
+  prog: &'a BBProgram,
+  out: T,
+  input_args: &[String],
+  profiling_out: U,
+  options: &'a InterpOptions,
+) -> Result<(State<'a, T, U>, &'a BBFunction), PositionalInterpError> {
+  let main_func = get_main(prog)?;
+  let state = start_function(prog, main_func, out, input_args, profiling_out, options)?;
+  Ok((state, main_func))
+}
+
+// Parses the arguments of `main_func`, which does not have to be "main", and sets up the state to execute it with an empty heap of the backend `H`
+fn start_function<'a, T: std::io::Write, U: std::io::Write, H: HeapBackend>(
+  prog: &'a BBProgram,
+  main_func: &'a BBFunction,
+  out: T,
+  input_args: &[String],
+  mut profiling_out: U,
+  options: &'a InterpOptions,
+) -> Result<State<'a, T, U, H>, PositionalInterpError> {
+  let mut env = Environment::new(main_func.num_of_vars);
+  let heap = CheckedHeap::new(H::default(), options);
+
+  env = parse_args(
+    env,
+    &main_func.args,
+    &main_func.args_as_nums,
+    input_args,
+    options.int_literals,
+  )
+  .map_err(|e| e.add_pos(main_func.pos))?;
+
+  if options.echo_args {
+    writeln!(
+      profiling_out,
+      "input:{}",
+      main_func
+        .args
+        .iter()
+        .zip(main_func.args_as_nums.iter())
+        .map(|(a, i)| format!(" {}:{}={}", a.name, a.arg_type, env.get(i)))
+        .collect::<String>()
+    )
+    .and_then(|_| profiling_out.flush())
+    .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  let mut state = State::new(prog, options, env, heap, out, profiling_out);
+  if options.taint {
+    state.taint.taint_inputs(main_func.args.len());
+  }
+  Ok(state)
+}
+
+/// A call made by the function run with [`execute_function_with_stubs`] that went to a stub
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StubbedCall {
+  /// The name of the function that was called
+  pub callee: String,
+  /// The arguments of the call as `print` would show them
+  pub args: Vec<String>,
+}
+
+/// Runs the function ```func_name``` of ```prog``` on its own with every call that it makes answered by a stub, for unit testing it
+///
+/// Returns what the function returned and the calls that it made, in order. The callees are never executed.
+///
+/// ```stubs``` maps the name of a callee to what calls to it return. A stub of `Some` literal returns that literal, which must have the return type of the callee. A stub of `None` returns nothing from a callee without a return type and otherwise returns the zero value of its type, i.e. `0`, `false` or `0.0`, or a pointer which does not point into any allocation. A callee without a return type can only be given a stub of `None`. Calling a function that does not have a stub is an error, and that includes ```func_name``` calling itself. ```input_args``` are parsed against the arguments of ```func_name``` the same way as the arguments of "main", and the function may have a return type. The end of execution checks, such as for leaked memory, are done as for "main"
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp};
+/// use bril_rs::{Literal, Program};
+///
+/// let src = "@main {\n  x: int = const 1;\n  y: int = call @scaled x;\n}\n@scaled(n: int): int {\n  f: int = call @factor n;\n  r: int = mul n f;\n  print r;\n  ret r;\n}\n@factor(n: int): int {\n  ret n;\n}";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), true)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+///
+/// let run = |stubs| {
+///   let mut out = Vec::new();
+///   interp::execute_function_with_stubs(
+///     &bbprog,
+///     "scaled",
+///     &mut out,
+///     &["4".to_string()],
+///     std::io::sink(),
+///     &interp::InterpOptions::default(),
+///     &stubs,
+///   )
+///   .map(|result| (out, result))
+///   .map_err(|e| e.to_string())
+/// };
+///
+/// let (out, (returned, calls)) =
+///   run([("factor".to_string(), Some(Literal::Int(10)))].into_iter().collect()).unwrap();
+/// assert_eq!(out, b"40\n");
+/// assert_eq!(returned, Some(brilirs::record::FieldValue::Int(40)));
+/// assert_eq!(
+///   calls,
+///   [interp::StubbedCall { callee: "factor".to_string(), args: vec!["4".to_string()] }]
+/// );
+/// assert_eq!(
+///   run(Default::default()).unwrap_err(),
+///   "Line 6, Column 3: `@factor` was called but it does not have a stub"
+/// );
+/// ```
+pub fn execute_function_with_stubs<T: std::io::Write, U: std::io::Write>(
+  prog: &BBProgram,
+  func_name: &str,
+  out: T,
+  input_args: &[String],
+  profiling_out: U,
+  options: &InterpOptions,
+  stubs: &FxHashMap<String, Option<bril_rs::Literal>>,
+) -> Result<(Option<FieldValue>, Vec<StubbedCall>), PositionalInterpError> {
+  let func = prog
+    .func_index
+    .iter()
+    .find(|f| f.name == func_name)
+    .ok_or_else(|| PositionalInterpError::new(InterpError::FuncNotFound(func_name.to_string())))?;
+  let mut state: State<_, _> = start_function(prog, func, out, input_args, profiling_out, options)?;
+  state.stubs = Some(stubs);
+  let result = run(&mut state, func);
+  let returned = result
+    .as_ref()
+    .ok()
+    .and_then(Option::as_ref)
+    .and_then(FieldValue::from_value);
+  let calls = std::mem::take(&mut state.stubbed_calls);
+  finish_main(&mut state, func, result)?;
+  Ok((returned, calls))
+}
+
+/// Resumes the execution captured in ```checkpoint```, which must have been taken from the same ```prog```. Like [`execute_main_until`], execution is suspended again if ```checkpoint_at``` is reached.
+pub fn resume_main_until<T: std::io::Write, U: std::io::Write>(
+  prog: &BBProgram,
+  out: T,
+  profiling_out: U,
+  options: &InterpOptions,
+  checkpoint: Checkpoint,
+  checkpoint_at: Option<u32>,
+) -> Result<Option<Checkpoint>, PositionalInterpError> {
+  let main_func = get_main(prog)?;
+
+  let Checkpoint {
+    instruction_count,
+    unique_ids,
+    frames,
+    env,
+    heap,
+    frame_allocs,
+  } = checkpoint;
+
+  if frames.first().map(|f| &f.func) != Some(&main_func.name) {
+    return Err(PositionalInterpError::new(InterpError::BadCheckpoint(
+      "the outermost frame is not \"main\"".to_string(),
+    )));
+  }
+
+  // The checks have not seen the allocations made before the checkpoint so they are not done
+  let mut state = State::new(
+    prog,
+    options,
+    env,
+    CheckedHeap::unchecked(heap),
+    out,
+    profiling_out,
+  );
+  state.instruction_count = instruction_count;
+  state.unique_ids = unique_ids;
+  state.checkpoint_at = checkpoint_at;
+  state.frame_allocs = frame_allocs;
+
+  let result = resume_frames(&mut state, &frames);
+
+  suspend_or_finish(state, main_func, result).map(|(checkpoint, _)| checkpoint)
+}
+
+fn get_main(prog: &BBProgram) -> Result<&BBFunction, PositionalInterpError> {
+  let main_func = prog
+    .index_of_main
+    .map(|i| prog.get(i).unwrap())
+    .ok_or_else(|| PositionalInterpError::new(InterpError::NoMainFunction))?;
+
+  if let Some(return_type) = &main_func.return_type {
+    return Err(InterpError::NonEmptyRetForFunc(
+      main_func.name.clone(),
+      Some(return_type.clone()),
+    ))
+    .map_err(|e| e.add_pos(main_func.pos));
+  }
+  Ok(main_func)
+}
+
+// Where an instruction is in the source as ` at line 3, column 5`, to follow what it describes in a message. This is empty if it is not known
+pub(crate) fn at_pos(pos: Option<Position>) -> String {
+  pos.map_or_else(String::new, |p| {
+    format!(" at line {}, column {}", p.row, p.col)
+  })
+}
+
+// Where something is in the source, as the `line` and `column` fields of the JSON object that it is flattened into. They are left out if it is not known
+#[derive(Serialize)]
+pub(crate) struct JsonPos {
+  line: u64,
+  column: u64,
+}
+
+impl From<Position> for JsonPos {
+  fn from(p: Position) -> Self {
+    Self {
+      line: p.row,
+      column: p.col,
+    }
+  }
+}
+
+// An event of the Trace Event Format. Everything runs in the one process and thread
+#[derive(Serialize)]
+struct TraceEvent<'a> {
+  name: &'a str,
+  ph: char,
+  ts: u32,
+  pid: u32,
+  tid: u32,
+}
+
+// Writes `events` as a JSON array in the Trace Event Format that chrome://tracing and Perfetto load, one event per line. Timestamps are instruction counts rather than microseconds
+fn write_chrome_trace<W: std::io::Write>(
+  mut w: W,
+  events: &[(char, &str, u32)],
+) -> std::io::Result<()> {
+  writeln!(w, "[")?;
+  for (i, &(ph, name, ts)) in events.iter().enumerate() {
+    let event = TraceEvent {
+      name,
+      ph,
+      ts,
+      pid: 1,
+      tid: 1,
+    };
+    serde_json::to_writer(&mut w, &event)?;
+    writeln!(w, "{}", if i + 1 == events.len() { "" } else { "," })?;
+  }
+  writeln!(w, "]")?;
+  w.flush()
+}
+
+impl<T: std::io::Write, U: std::io::Write, H: HeapBackend> State<'_, T, U, H> {
+  // Records that `dest` in the current frame was written by the instruction at `pos`
+  fn record_definition(&mut self, dest: usize, pos: Option<Position>) {
+    let idx = self.env.current_pointer + dest;
+    if idx >= self.def_positions.len() {
+      self.def_positions.resize(idx + 1, None);
+    }
+    self.def_positions[idx] = Some(pos);
+  }
+
+  // Warns when `free` is given the allocation that a call returned while something other than `freed`, the variable given to `free`, still points into it
+  fn check_dangling_return(
+    &mut self,
+    func: &BBFunction,
+    freed: usize,
+    pos: Option<Position>,
+  ) -> Result<(), InterpError> {
+    let base = <&Pointer>::from(self.env.get(&freed)).base;
+    let Some(callee) = self.returned_bases.remove(&base) else {
+      return Ok(());
+    };
+    let points_here = |v: &Value| matches!(v, Value::Pointer(p) if p.base == base);
+    let mut holders: Vec<_> = (0..func.num_of_vars)
+      .filter(|v| *v != freed && points_here(self.env.get(v)))
+      .map(|v| format!("`{}`", func.var_names[v]))
+      .collect();
+    if self.env.env[..self.env.current_pointer]
+      .iter()
+      .any(points_here)
+    {
+      holders.push("a caller".to_string());
+    }
+    if self
+      .heap
+      .bases()
+      .into_iter()
+      .any(|b| b != base && self.heap.block(b).unwrap().iter().any(points_here))
+    {
+      holders.push("the heap".to_string());
+    }
+    if holders.is_empty() {
+      return Ok(());
+    }
+    let at = at_pos(pos);
+    writeln!(
+      self.profiling_out,
+      "warning: `free`{at} frees the allocation returned by @{callee} while it is still reachable from {}",
+      holders.join(", ")
+    )
+    .and_then(|_| self.profiling_out.flush())
+    .map_err(|e| InterpError::IoError(Box::new(e)))
+  }
+
+  // Reports an error if storing a pointer into the allocation `stored` to the allocation `into` would let it outlive what it points to
+  fn check_escape(&self, into: usize, stored: usize) -> Result<(), InterpError> {
+    let depth = |base| {
+      self
+        .frame_allocs
+        .iter()
+        .find(|(_, b)| *b == base)
+        .map(|(d, _)| *d)
+    };
+    match (depth(stored), depth(into)) {
+      // A heap allocation or the frame allocation of a caller
+      (Some(stored_depth), into_depth) if into_depth.is_none_or(|d| d < stored_depth) => {
+        Err(InterpError::EscapingStackPointer(stored, into))
+      }
+      _ => Ok(()),
+    }
+  }
+
+  // Records a call of the function with index `callee` with `args` if it is pure, or reports an error if it is already running with the same arguments. Returns whether it was recorded, in which case `leave_pure_call` must be called once it returns
+  fn enter_pure_call(&mut self, callee: usize, args: &[usize]) -> Result<bool, InterpError> {
+    if !self.pure_funcs.contains(&callee) {
+      return Ok(false);
+    }
+    let key = |v: &Value| match v {
+      // Every bit is kept since a pure function can tell apart floats that `feq` finds equal, such as `0.0` and `-0.0`
+      Value::Float(f) => Some(ValueKey::Float(f.to_bits() as i64)),
+      v => FieldValue::from_value(v).map(|v| canonical_key(&v)),
+    };
+    let call = (callee, args.iter().map(|a| key(self.env.get(a))).collect());
+    if !self.active_pure_calls.insert(call.clone()) {
+      return Err(InterpError::InfiniteRecursionDetected(
+        self.prog.get(callee).unwrap().name.clone(),
+      ));
+    }
+    self.pure_calls.push(call);
+    Ok(true)
+  }
+
+  fn leave_pure_call(&mut self) {
+    let call = self.pure_calls.pop().unwrap();
+    self.active_pure_calls.remove(&call);
+  }
+
+  // Calls the native function with index `callee` with `args` and returns what it returns for a destination of type `dest_type`
+  fn native_call(
+    &self,
+    callee: usize,
+    args: &[usize],
+    dest_type: Option<&bril_rs::Type>,
+  ) -> Result<Option<Value>, InterpError> {
+    let name = &self.prog.native_names[callee - self.prog.func_index.len()];
+    let native = self
+      .natives
+      .and_then(|natives| natives.get(name))
+      .ok_or_else(|| InterpError::FuncNotFound(name.clone()))?;
+    let args = args
+      .iter()
+      .enumerate()
+      .map(|(i, a)| {
+        FieldValue::from_value(self.env.get(a))
+          .ok_or_else(|| InterpError::NativeUndefinedArg(name.clone(), i))
+      })
+      .collect::<Result<Vec<_>, _>>()?;
+    let result = native(&args);
+    dest_type
+      .map(|ty| {
+        result.to_value(ty).ok_or_else(|| {
+          InterpError::NativeReturnTypeMismatch(
+            name.clone(),
+            result.type_name().to_string(),
+            ty.clone(),
+          )
+        })
+      })
+      .transpose()
+  }
+
+  // Records a call of `callee` with `args` and returns what its stub returns instead of executing it
+  fn stub_call(
+    &mut self,
+    stubs: &FxHashMap<String, Option<bril_rs::Literal>>,
+    callee: &BBFunction,
+    args: &[usize],
+  ) -> Result<Option<Value>, InterpError> {
+    let stub = stubs
+      .get(&callee.name)
+      .ok_or_else(|| InterpError::UnstubbedCall(callee.name.clone()))?;
+    let result = match (stub, &callee.return_type) {
+      (None, None) => None,
+      (None, Some(ty)) => Some(undef_value(ty, false)),
+      (Some(lit), Some(ty)) if lit.get_type() == *ty => Some(Value::from(lit)),
+      (Some(lit), ty) => {
+        return Err(InterpError::ReturnTypeMismatch(
+          callee.name.clone(),
+          ty.clone(),
+          Some(lit.get_type()),
+        ))
+      }
+    };
+    self.stubbed_calls.push(StubbedCall {
+      callee: callee.name.clone(),
+      args: args.iter().map(|a| self.env.get(a).to_string()).collect(),
+    });
+    Ok(result)
+  }
+
+  // Outputs a warning for an `add`, `sub` or `mul` at `pos` that wrapped around to `res` and counts it
+  #[cold]
+  fn warn_wraparound(
+    &mut self,
+    op: bril_rs::ValueOps,
+    arg0: i64,
+    arg1: i64,
+    res: i64,
+    pos: Option<Position>,
+  ) -> Result<(), InterpError> {
+    self.wraparounds += 1;
+    let at = at_pos(pos);
+    writeln!(
+      self.profiling_out,
+      "warning: `{op}`{at} wrapped around: {arg0} and {arg1} gave {res}"
+    )
+    .and_then(|_| self.profiling_out.flush())
+    .map_err(|e| InterpError::IoError(Box::new(e)))
+  }
+
+  // Outputs where each variable printed by the `print` at `pos` in `func` was last assigned
+  fn write_provenance(
+    &mut self,
+    func: &BBFunction,
+    args: &[usize],
+    pos: Option<Position>,
+  ) -> Result<(), InterpError> {
+    for a in args {
+      let from = match self.def_positions.get(self.env.current_pointer + a) {
+        Some(Some(def)) => format!("assigned{}", at_pos(*def)),
+        _ => format!("argument of @{}", func.name),
+      };
+      writeln!(
+        self.profiling_out,
+        "print `{}`{}: {from}",
+        func.var_names[*a],
+        at_pos(pos)
+      )
+      .map_err(|e| InterpError::IoError(Box::new(e)))?;
+    }
+    self
+      .profiling_out
+      .flush()
+      .map_err(|e| InterpError::IoError(Box::new(e)))
+  }
+}
+
+// The version of the object written for ```json_summary```, to be bumped whenever a field changes meaning or is removed
+const JSON_SUMMARY_VERSION: u32 = 1;
+
+// The object written for ```json_summary```. Every field other than `version` and `leaked_bases` is `None` when its option is not enabled and is then left out
+#[derive(Serialize)]
+struct JsonSummary<'a> {
+  version: u32,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  total_dyn_inst: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  self_ids: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  wraparounds: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  call_returns: Option<Vec<JsonCallReturn<'a>>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  dead_stores: Option<Vec<crate::dead_stores::JsonDeadStore<'a>>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  redundant_loads: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  store_forwardable_loads: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  polymorphic: Option<Vec<JsonPolymorphic<'a>>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  max_live_slots: Option<BTreeMap<&'a str, usize>>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  alloc_sites: Option<Vec<crate::alloc_sites::JsonAllocSite<'a>>>,
+  leaked_bases: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct JsonCallReturn<'a> {
+  function: &'a str,
+  value: String,
+}
+
+#[derive(Serialize)]
+struct JsonPolymorphic<'a> {
+  function: &'a str,
+  variable: &'a str,
+  types: &'a BTreeSet<&'static str>,
+}
+
+impl<T: std::io::Write, U: std::io::Write, H: HeapBackend> State<'_, T, U, H> {
+  // The number of instructions reported by profiling
+  fn profiled_total(&self) -> u64 {
+    if self.options.profile_function.is_some() {
+      self.profiled_count
+    } else {
+      u64::from(self.instruction_count)
+    }
+  }
+
+  // The number of instructions counted by profiling with each opcode, in alphabetical order
+  fn instruction_mix(&self) -> BTreeMap<String, u64> {
+    let mut mix = BTreeMap::new();
+    for ((func, block), count) in &self.block_counts {
+      let func = self
+        .prog
+        .func_index
+        .iter()
+        .find(|f| f.name == *func)
+        .unwrap();
+      for instr in &func.blocks[*block].instrs {
+        *mix.entry(opcode(instr)).or_default() += count;
+      }
+    }
+    mix
+  }
+
+  // Everything that was measured as a single line JSON object, with a field for each metric that is enabled
+  fn json_summary(&self) -> String {
+    let o = self.options;
+    let summary = JsonSummary {
+      version: JSON_SUMMARY_VERSION,
+      total_dyn_inst: o.profiling.then(|| self.profiled_total()),
+      self_ids: (o.profiling && o.self_ids).then_some(self.self_ids),
+      wraparounds: (o.profiling && o.wraparound_warnings).then_some(self.wraparounds),
+      call_returns: o.capture_returns.then(|| {
+        self
+          .call_returns
+          .iter()
+          .filter_map(|(name, value)| {
+            value.as_ref().map(|v| JsonCallReturn {
+              function: name,
+              value: v.to_string(),
+            })
+          })
+          .collect()
+      }),
+      dead_stores: o.dead_stores.then(|| self.dead_stores.json()),
+      redundant_loads: o.redundant_loads.then_some(self.redundant_loads),
+      store_forwardable_loads: o
+        .store_forwardable_loads
+        .then_some(self.store_forwardable_loads),
+      polymorphic: o.dynamic_types.then(|| {
+        sorted_entries(&self.dynamic_types)
+          .into_iter()
+          .filter(|(_, types)| types.len() > 1)
+          .map(|(&(function, variable), types)| JsonPolymorphic {
+            function,
+            variable,
+            types,
+          })
+          .collect()
+      }),
+      max_live_slots: o.live_slots.then(|| {
+        self
+          .max_live_slots
+          .iter()
+          .map(|(&name, &max)| (name, max))
+          .collect()
+      }),
+      alloc_sites: o.alloc_sites.then(|| self.alloc_sites.json()),
+      leaked_bases: self.heap.bases(),
+    };
+    serde_json::to_string(&summary).expect("the summary only has maps with string keys")
+  }
+}
+
+// Either packages up a suspended execution into a Checkpoint or does the end of execution checks and reporting
+fn suspend_or_finish<T: std::io::Write, U: std::io::Write>(
+  mut state: State<T, U>,
+  main_func: &BBFunction,
+  result: Result<Option<Value>, PositionalInterpError>,
+) -> Result<(Option<Checkpoint>, u32), PositionalInterpError> {
+  if state.checkpoint_frames.is_empty() {
+    return finish_main(&mut state, main_func, result).map(|count| (None, count));
+  }
+  flush_output(&mut state)?;
+  state.checkpoint_frames.reverse();
+  Ok((
+    Some(Checkpoint {
+      instruction_count: state.instruction_count,
+      unique_ids: state.unique_ids,
+      frames: state.checkpoint_frames,
+      env: state.env,
+      heap: state.heap.inner,
+      frame_allocs: state.frame_allocs,
+    }),
+    state.instruction_count,
+  ))
+}
+
+// Outputs what was printed but not written or flushed yet
+fn flush_output<T: std::io::Write, U: std::io::Write, H: HeapBackend>(
+  state: &mut State<T, U, H>,
+) -> Result<(), PositionalInterpError> {
+  // These were printed before execution stopped so they are output whatever the result
+  while let Some(line) = state.held_lines.pop_front() {
+    state.emit(&line).map_err(PositionalInterpError::new)?;
+  }
+  // Whatever `flush_every` or `OutputBuffering::End` has not flushed yet
+  state
+    .out
+    .flush()
+    .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))
+}
+
+// Does the end of execution checks and reporting of a run that was not suspended and returns the number of dynamic instructions that it executed
+fn finish_main<T: std::io::Write, U: std::io::Write, H: HeapBackend>(
+  state: &mut State<T, U, H>,
+  main_func: &BBFunction,
+  result: Result<Option<Value>, PositionalInterpError>,
+) -> Result<u32, PositionalInterpError> {
+  flush_output(state)?;
+  result?;
+
+  // Replaces the text output of the metrics that it covers
+  let text = !state.options.json_summary;
+  // This is output before checking for leaks since it includes what tracks them down
+  if state.options.json_summary {
+    writeln!(state.profiling_out, "{}", state.json_summary())
+      .and_then(|_| state.profiling_out.flush())
+      .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  // This is output before checking for leaks since it is what tracks them down
+  if text && state.options.alloc_sites {
+    state
+      .alloc_sites
+      .report()
+      .iter()
+      .try_for_each(|line| writeln!(state.profiling_out, "{line}"))
+      .and_then(|_| state.profiling_out.flush())
+      .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  if !state.heap.is_empty() {
+    return Err(InterpError::MemLeak).map_err(|e| e.add_pos(main_func.pos));
+  }
+
+  if text && state.options.profiling && state.options.profile_format == ProfileFormat::Csv {
+    let mix = state.instruction_mix();
+    writeln!(state.profiling_out, "opcode,count")
+      .and_then(|_| {
+        mix
+          .iter()
+          .try_for_each(|(op, count)| writeln!(state.profiling_out, "{op},{count}"))
+      })
+      .and_then(|_| writeln!(state.profiling_out, "total,{}", mix.values().sum::<u64>()))
+      .and_then(|_| state.profiling_out.flush())
+      .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  } else if text && state.options.profiling {
+    writeln!(
+      state.profiling_out,
+      "total_dyn_inst: {}",
+      state.profiled_total()
+    )
+    .and_then(|_| {
+      if state.options.self_ids {
+        writeln!(state.profiling_out, "self_ids: {}", state.self_ids)
+      } else {
+        Ok(())
+      }
+    })
+    .and_then(|_| {
+      if state.options.wraparound_warnings {
+        writeln!(state.profiling_out, "wraparounds: {}", state.wraparounds)
+      } else {
+        Ok(())
+      }
+    })
+    // We call flush here in case `profiling_out` is a https://doc.rust-lang.org/std/io/struct.BufWriter.html
+    // Otherwise we would expect this flush to be a nop.
+    .and_then(|_| state.profiling_out.flush())
+    .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  if text
+    && state
+      .options
+      .print_throttle
+      .is_some_and(|t| t.policy == ThrottlePolicy::Drop)
+  {
+    writeln!(
+      state.profiling_out,
+      "dropped_lines: {}",
+      state.dropped_lines
+    )
+    .and_then(|_| state.profiling_out.flush())
+    .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  if text && state.options.capture_returns {
+    state
+      .call_returns
+      .iter()
+      .try_for_each(|(name, value)| match value {
+        Some(v) => writeln!(state.profiling_out, "call @{name} returned {v}"),
+        None => Ok(()),
+      })
+      .and_then(|_| state.profiling_out.flush())
+      .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  if text && state.options.dead_stores {
+    state
+      .dead_stores
+      .report()
+      .iter()
+      .try_for_each(|line| writeln!(state.profiling_out, "{line}"))
+      .and_then(|_| state.profiling_out.flush())
+      .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  if text && state.options.redundant_loads {
+    writeln!(
+      state.profiling_out,
+      "redundant_loads: {}",
+      state.redundant_loads
+    )
+    .and_then(|_| state.profiling_out.flush())
+    .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  if text && state.options.store_forwardable_loads {
+    writeln!(
+      state.profiling_out,
+      "store_forwardable_loads: {}",
+      state.store_forwardable_loads
+    )
+    .and_then(|_| state.profiling_out.flush())
+    .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  if text && state.options.dynamic_types {
+    sorted_entries(&state.dynamic_types)
+      .iter()
+      .filter(|(_, types)| types.len() > 1)
+      .try_for_each(|((func, var), types)| {
+        let types: Vec<_> = types.iter().copied().collect();
+        writeln!(
+          state.profiling_out,
+          "polymorphic `{var}` in @{func}: {}",
+          types.join(", ")
+        )
+      })
+      .and_then(|_| state.profiling_out.flush())
+      .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  if let Some(sizes) = &state.heap.alloc_sizes {
+    sorted_entries(sizes)
+      .iter()
+      .try_for_each(|(size, count)| writeln!(state.profiling_out, "alloc_size {size}: {count}"))
+      .and_then(|_| state.profiling_out.flush())
+      .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  if text && state.options.live_slots {
+    sorted_entries(&state.max_live_slots)
+      .iter()
+      .try_for_each(|(name, max)| writeln!(state.profiling_out, "max_live_slots @{name}: {max}"))
+      .and_then(|_| state.profiling_out.flush())
+      .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  if let Some(source) = &state.options.lcov {
+    write!(
+      state.profiling_out,
+      "{}",
+      state.coverage.lcov(state.prog, source)
+    )
+    .and_then(|_| state.profiling_out.flush())
+    .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  if state.options.chrome_trace {
+    write_chrome_trace(&mut state.profiling_out, &state.trace_events)
+      .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  if state.options.flamegraph {
+    sorted_entries(&state.folded_stacks)
+      .iter()
+      .try_for_each(|(stack, count)| writeln!(state.profiling_out, "{} {count}", stack.join(";")))
+      .and_then(|_| state.profiling_out.flush())
+      .map_err(|e| PositionalInterpError::new(InterpError::IoError(Box::new(e))))?;
+  }
+
+  Ok(state.instruction_count)
 }
@@ -0,0 +1,64 @@
+use crate::record::FieldValue;
+
+/// A form of a [`FieldValue`] that can be hashed and ordered, for using values as the keys of a map or the elements of a set
+///
+/// Ints, bools and pointers are keyed by their values. Floats are keyed so that two floats are the same key exactly when `feq` finds them equal, except that NaN is a key like any other: `0.0` and `-0.0` are the same key, and every NaN is the same key, whatever its sign or payload, so a NaN that is put into a map can be found again. Floats are otherwise ordered as by [`f64::total_cmp`], with NaN after positive infinity. Keys of different types are never equal and are ordered ints, then bools, then floats, then pointers.
+///
+/// ```
+/// use brilirs::{key::canonical_key, record::FieldValue};
+///
+/// assert_eq!(canonical_key(&FieldValue::Float(0.0)), canonical_key(&FieldValue::Float(-0.0)));
+/// assert_eq!(
+///   canonical_key(&FieldValue::Float(f64::NAN)),
+///   canonical_key(&FieldValue::Float(-f64::NAN))
+/// );
+/// assert!(canonical_key(&FieldValue::Float(f64::INFINITY)) < canonical_key(&FieldValue::Float(f64::NAN)));
+/// assert!(canonical_key(&FieldValue::Float(-1.5)) < canonical_key(&FieldValue::Float(-0.0)));
+/// assert_ne!(canonical_key(&FieldValue::Int(1)), canonical_key(&FieldValue::Float(1.0)));
+///
+/// let mut set = std::collections::HashSet::new();
+/// set.insert(canonical_key(&FieldValue::Float(f64::NAN)));
+/// assert!(set.contains(&canonical_key(&FieldValue::Float(0.0 / 0.0))));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ValueKey {
+  /// An `int`
+  Int(i64),
+  /// A `bool`
+  Bool(bool),
+  /// A `float`, as the bits of its canonical form rearranged so that comparing them as integers is the same as [`f64::total_cmp`]
+  Float(i64),
+  /// A pointer, given by the base of its allocation and its offset into it
+  Pointer {
+    /// Which allocation the pointer is into
+    base: usize,
+    /// How many cells into the allocation the pointer is
+    offset: i64,
+  },
+}
+
+/// The [`ValueKey`] of ```value```
+#[must_use]
+pub fn canonical_key(value: &FieldValue) -> ValueKey {
+  match value {
+    FieldValue::Int(i) => ValueKey::Int(*i),
+    FieldValue::Bool(b) => ValueKey::Bool(*b),
+    FieldValue::Float(f) => {
+      let f = if f.is_nan() {
+        f64::NAN
+      } else if *f == 0.0 {
+        // -0.0 == 0.0
+        0.0
+      } else {
+        *f
+      };
+      let bits = f.to_bits() as i64;
+      // The same transformation that total_cmp makes, which flips every bit but the sign of a negative float so that more negative floats are smaller
+      ValueKey::Float(bits ^ (((bits >> 63) as u64) >> 1) as i64)
+    }
+    FieldValue::Pointer { base, offset } => ValueKey::Pointer {
+      base: *base,
+      offset: *offset,
+    },
+  }
+}
@@ -6,42 +6,271 @@
 use std::error::Error;
 
 use basic_block::BBProgram;
-use bril_rs::Program;
+use bril_rs::{Program, ValueOps};
+use checkpoint::Checkpoint;
+use cli::Cli;
+use debugger::Debugger;
+use interp::{
+  ArithmeticMode, ComparisonPolicy, DivisionMode, IntLiteralPolicy, InterpOptions, OutputBuffering,
+  PrintThrottle, ProfileFormat, RoundingMode, ThrottlePolicy,
+};
 
+mod alloc_sites;
 /// The internal representation of brilirs, provided a ```TryFrom<Program>``` conversion
 pub mod basic_block;
+/// Provides [`cache::RunCache`] for replaying the output of a [Program] that is run again with the same arguments
+pub mod cache;
 /// Provides ```check::type_check``` to validate [Program]
 pub mod check;
+/// Provides [`checkpoint::Checkpoint`] for suspending and resuming the execution of a [Program]
+pub mod checkpoint;
 #[doc(hidden)]
 pub mod cli;
+mod coverage;
+mod dead_stores;
+mod debugger;
+/// Provides ```BBProgram::canonical_dump``` for comparing the representation of a [Program] in tests and ```BBProgram::to_json``` for inspecting it from other tools
+pub mod dump;
 mod error;
+pub use error::ErrorKind;
+/// Provides ```BBProgram::constant_fold``` for folding the constants of a [Program]
+pub mod fold;
+/// Provides ```harness::run_expect``` for checking the output of a [Program] in tests
+pub mod harness;
 /// Provides ```interp::execute_main``` to execute [Program] that have been converted into [BBProgram]
 pub mod interp;
+/// Provides [`key::canonical_key`] for using values as the keys of a map or set
+pub mod key;
+/// Provides [`native::NativeFunctions`] for calling functions written in Rust from a [Program]
+pub mod native;
+/// Provides ```Checkpoint::read_record``` for inspecting records in the memory of a [Program]
+pub mod record;
+/// Provides [`semantics::Semantics`] for replacing the arithmetic and comparisons of the interpreter
+pub mod semantics;
+mod sites;
+mod taint;
+/// Provides [`tee::Tee`] for sending the output of a [Program] to multiple writers
+pub mod tee;
 
 #[doc(hidden)]
 pub fn run_input<T: std::io::Write, U: std::io::Write>(
   input: Box<dyn std::io::Read>,
-  out: T,
-  input_args: Vec<String>,
-  profiling: bool,
+  mut out: T,
   profiling_out: U,
-  check: bool,
-  text: bool,
+  args: &Cli,
 ) -> Result<(), Box<dyn Error>> {
   // It's a little confusing because of the naming conventions.
   //      - bril_rs takes file.json as input
   //      - bril2json takes file.bril as input
-  let prog: Program = if text {
+  let int_literals = match args.int_literals {
+    cli::IntLiterals::Error => IntLiteralPolicy::Error,
+    cli::IntLiterals::Wrap => IntLiteralPolicy::Wrap,
+  };
+  let prog: Program = if args.text && int_literals == IntLiteralPolicy::Wrap {
+    bril2json::parse_abstract_program_from_read_wrapping(input, true).try_into()?
+  } else if args.text {
     bril2json::parse_abstract_program_from_read(input, true).try_into()?
   } else {
     bril_rs::load_abstract_program_from_read(input).try_into()?
   };
-  let bbprog: BBProgram = prog.try_into()?;
-  check::type_check(&bbprog)?;
+  // Unwraps errors that already have a position so that they are shown with it
+  let mut bbprog = BBProgram::with_int_literals(prog, args.max_blocks, int_literals)
+    .map_err(|e| e.add_pos(None))?;
+  if args.relaxed_calls {
+    check::type_check_relaxed_calls(&bbprog)?;
+  } else {
+    check::type_check(&bbprog)?;
+  }
+  if args.ssa {
+    check::check_ssa(&bbprog)?;
+  }
+  let rounding = match args.rounding {
+    cli::Rounding::Nearest => RoundingMode::Nearest,
+    cli::Rounding::TowardZero => RoundingMode::TowardZero,
+    cli::Rounding::TowardPositive => RoundingMode::TowardPositive,
+    cli::Rounding::TowardNegative => RoundingMode::TowardNegative,
+  };
+  let division = match args.division {
+    cli::Division::Truncating => DivisionMode::Truncating,
+    cli::Division::Flooring => DivisionMode::Flooring,
+  };
+  if args.constant_fold {
+    // Folded with the options that affect the results so that the program still prints the same
+    bbprog = bbprog.constant_fold(&InterpOptions {
+      rounding,
+      division,
+      ..InterpOptions::default()
+    });
+  }
+  if args.dump_program {
+    write!(out, "{}", bbprog.canonical_dump())?;
+    out.flush()?;
+    return Ok(());
+  }
 
-  if !check {
-    interp::execute_main(&bbprog, out, &input_args, profiling, profiling_out)?;
+  if !args.check {
+    let options = InterpOptions {
+      profiling: args.profile,
+      profile_function: args.profile_function.clone(),
+      profile_callees: args.profile_callees,
+      self_ids: args.self_ids,
+      echo_args: args.echo_args,
+      capture_returns: args.capture_returns,
+      flamegraph: args.flamegraph,
+      flamegraph_interval: args.flamegraph_interval,
+      live_slots: args.live_slots,
+      arithmetic: if args.checked {
+        ArithmeticMode::Checked
+      } else {
+        ArithmeticMode::Wrapping
+      },
+      // Checked is inserted last so that it wins for an operation in both lists
+      arithmetic_overrides: args
+        .wrapping_ops
+        .iter()
+        .map(|op| (op, ArithmeticMode::Wrapping))
+        .chain(
+          args
+            .checked_ops
+            .iter()
+            .map(|op| (op, ArithmeticMode::Checked)),
+        )
+        .map(|(op, mode)| {
+          let op = match op {
+            cli::ArithmeticOp::Add => ValueOps::Add,
+            cli::ArithmeticOp::Sub => ValueOps::Sub,
+            cli::ArithmeticOp::Mul => ValueOps::Mul,
+            cli::ArithmeticOp::Gcd => ValueOps::Gcd,
+            cli::ArithmeticOp::Lcm => ValueOps::Lcm,
+          };
+          (op, mode)
+        })
+        .collect(),
+      rounding,
+      division,
+      comparison: match args.comparison {
+        cli::Comparison::Strict => ComparisonPolicy::Strict,
+        cli::Comparison::Numeric => ComparisonPolicy::Numeric,
+      },
+      int_literals,
+      roundtrip_check: args.roundtrip_check,
+      numeric_bools: args.numeric_bools,
+      print_inst_count: args.print_inst_count,
+      dump_uninit_reads: args.dump_uninit_reads,
+      chrome_trace: args.chrome_trace,
+      redundant_loads: args.redundant_loads,
+      store_forwardable_loads: args.store_forwardable_loads,
+      lifo_free: args.lifo_free,
+      typed_heap: args.typed_heap,
+      dump_on_error: args.dump_on_error,
+      trace_memory: args.trace_memory,
+      access_log: args.access_log,
+      trace_blocks: args.trace_blocks,
+      trace_instructions: args.trace_instructions,
+      strict_pointer_offsets: args.strict_pointer_offsets,
+      strict_pointer_bounds: args.strict_pointer_bounds,
+      strict_undef: args.strict_undef,
+      strict_uninit: args.strict_uninit,
+      poison_frames: args.poison_frames,
+      print_provenance: args.print_provenance,
+      dangling_returns: args.dangling_returns,
+      max_alloc_size: args.max_alloc_size,
+      max_block_transitions: args.max_block_transitions,
+      relaxed_calls: args.relaxed_calls,
+      taint: args.taint,
+      dead_stores: args.dead_stores,
+      dynamic_types: args.dynamic_types,
+      alloc_sites: args.alloc_sites,
+      // Coverage tools look for the source by this name
+      lcov: args
+        .lcov
+        .then(|| args.file.clone().unwrap_or_else(|| "-".to_string())),
+      json_summary: args.json_summary,
+      wraparound_warnings: args.warn_wraparound,
+      flush_every: Some(args.flush_every),
+      alloc_sizes: args.alloc_sizes,
+      check_escapes: args.check_escapes,
+      detect_infinite_recursion: args.detect_infinite_recursion,
+      profile_format: match args.profile_format {
+        cli::ProfileFormat::Text => ProfileFormat::Text,
+        cli::ProfileFormat::Csv => ProfileFormat::Csv,
+      },
+      output_buffering: match args.output_buffering {
+        cli::OutputBuffering::Line => OutputBuffering::Line,
+        cli::OutputBuffering::End => OutputBuffering::End,
+      },
+      print_throttle: args.print_limit.map(|lines| PrintThrottle {
+        lines,
+        window: args.print_window,
+        policy: match args.print_throttle {
+          cli::PrintThrottle::Drop => ThrottlePolicy::Drop,
+          cli::PrintThrottle::Block => ThrottlePolicy::Block,
+        },
+      }),
+    };
+
+    // Stdout only buffers up to the end of each line, so the output needs its own buffer to be written in fewer writes
+    let mut buffered;
+    let out: &mut dyn std::io::Write = if options.output_buffering == OutputBuffering::End {
+      buffered = std::io::BufWriter::new(&mut out);
+      &mut buffered
+    } else {
+      &mut out
+    };
+
+    if args.debug {
+      Debugger::new(std::io::stdin().lock(), std::io::stderr()).run(
+        &bbprog,
+        out,
+        &args.args,
+        profiling_out,
+        &options,
+      )?;
+      return Ok(());
+    }
+
+    if args.warm_up {
+      // Any error will be reported again by the measured run
+      let _ = run_once(&bbprog, std::io::sink(), std::io::sink(), &options, args);
+    }
+
+    let checkpoint = run_once(&bbprog, out, profiling_out, &options, args)?;
+
+    if let (Some(checkpoint), Some(path)) = (checkpoint, &args.checkpoint_file) {
+      checkpoint.save(path)?;
+    }
   }
 
   Ok(())
 }
+
+// Executes the program once from the start or from the checkpoint being restored
+fn run_once<T: std::io::Write, U: std::io::Write>(
+  bbprog: &BBProgram,
+  out: T,
+  profiling_out: U,
+  options: &InterpOptions,
+  args: &Cli,
+) -> Result<Option<Checkpoint>, Box<dyn Error>> {
+  Ok(match &args.restore {
+    None => interp::execute_main_until(
+      bbprog,
+      out,
+      &args.args,
+      profiling_out,
+      options,
+      args.checkpoint_at,
+    )?,
+    Some(path) => {
+      let checkpoint = Checkpoint::load(path)?;
+      interp::resume_main_until(
+        bbprog,
+        out,
+        profiling_out,
+        options,
+        checkpoint,
+        args.checkpoint_at,
+      )?
+    }
+  })
+}
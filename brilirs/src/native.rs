@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+
+use crate::record::FieldValue;
+
+// The signature of a function registered in `NativeFunctions`
+type NativeFunction = dyn Fn(&[FieldValue]) -> FieldValue;
+
+/// Functions written in Rust that a [`bril_rs::Program`] can call without defining them, to model a runtime library such as a math library
+///
+/// The program has to be converted with [`crate::basic_block::BBProgram::with_natives`] so that calls of these names are not rejected as calls of functions that do not exist, and run with [`crate::interp::execute_main_with_natives`]. A function that the program defines takes the place of a native function of the same name.
+///
+/// A native function is given the values of the arguments of the call in order and returns a value for the destination of the call, which must be of its type. The value is discarded for a call without a destination. Calls of native functions are not type checked, so a native function should check its arguments itself.
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp, native::NativeFunctions, record::FieldValue};
+/// use bril_rs::Program;
+///
+/// let mut natives = NativeFunctions::default();
+/// natives.register("sqrt", |args| match args {
+///   [FieldValue::Float(x)] => FieldValue::Float(x.sqrt()),
+///   _ => FieldValue::Float(f64::NAN),
+/// });
+///
+/// let src = "@main {\n  x: float = const 2.25;\n  r: float = call @sqrt x;\n  print r;\n}";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///   .try_into()
+///   .unwrap();
+/// let bbprog = BBProgram::with_natives(prog, &natives).unwrap();
+///
+/// let mut out = Vec::new();
+/// interp::execute_main_with_natives(
+///   &bbprog,
+///   &mut out,
+///   &[],
+///   std::io::sink(),
+///   &interp::InterpOptions::default(),
+///   &natives,
+/// )
+/// .unwrap();
+/// assert_eq!(out, b"1.5\n");
+/// ```
+#[derive(Default)]
+pub struct NativeFunctions {
+  funcs: BTreeMap<String, Box<NativeFunction>>,
+}
+
+impl std::fmt::Debug for NativeFunctions {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_set().entries(self.funcs.keys()).finish()
+  }
+}
+
+impl NativeFunctions {
+  /// Registers ```func``` to be called for calls of ```name```, replacing any function already registered under it
+  pub fn register(
+    &mut self,
+    name: &str,
+    func: impl Fn(&[FieldValue]) -> FieldValue + 'static,
+  ) -> &mut Self {
+    self.funcs.insert(name.to_string(), Box::new(func));
+    self
+  }
+
+  /// The names of the registered functions in alphabetical order
+  pub fn names(&self) -> impl Iterator<Item = &str> {
+    self.funcs.keys().map(String::as_str)
+  }
+
+  pub(crate) fn get(&self, name: &str) -> Option<&NativeFunction> {
+    self.funcs.get(name).map(Box::as_ref)
+  }
+}
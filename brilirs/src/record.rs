@@ -0,0 +1,127 @@
+use bril_rs::Type;
+
+use crate::checkpoint::Checkpoint;
+use crate::error::InterpError;
+use crate::interp::{HeapBackend, Pointer, Value};
+
+/// One field of a record in memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+  /// The number of cells from the start of the record to the field
+  pub offset: i64,
+  /// The type that the cell is expected to hold
+  pub field_type: Type,
+}
+
+/// The value of a [`Field`] that has been read from memory
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+  /// An `int`
+  Int(i64),
+  /// A `bool`
+  Bool(bool),
+  /// A `float`
+  Float(f64),
+  /// A pointer, given by the base of its allocation and its offset into it
+  Pointer {
+    /// Which allocation the pointer is into
+    base: usize,
+    /// How many cells into the allocation the pointer is
+    offset: i64,
+  },
+}
+
+impl FieldValue {
+  // The public form of a value on the heap, which is `None` if the cell has not been stored to
+  pub(crate) const fn from_value(value: &Value) -> Option<Self> {
+    match value {
+      Value::Int(v) => Some(Self::Int(*v)),
+      Value::Bool(v) => Some(Self::Bool(*v)),
+      Value::Float(v) => Some(Self::Float(*v)),
+      Value::Pointer(p) => Some(Self::Pointer {
+        base: p.base,
+        offset: p.offset,
+      }),
+      Value::Uninitialized => None,
+    }
+  }
+
+  // The value that the interpreter uses for it if it is of type `ty`
+  pub(crate) const fn to_value(&self, ty: &Type) -> Option<Value> {
+    match (self, ty) {
+      (Self::Int(v), Type::Int) => Some(Value::Int(*v)),
+      (Self::Bool(v), Type::Bool) => Some(Value::Bool(*v)),
+      (Self::Float(v), Type::Float) => Some(Value::Float(*v)),
+      (Self::Pointer { base, offset }, Type::Pointer(_)) => Some(Value::Pointer(Pointer {
+        base: *base,
+        offset: *offset,
+      })),
+      _ => None,
+    }
+  }
+
+  // The name of the Bril type of the value
+  pub(crate) const fn type_name(&self) -> &'static str {
+    match self {
+      Self::Int(_) => "int",
+      Self::Bool(_) => "bool",
+      Self::Float(_) => "float",
+      Self::Pointer { .. } => "ptr",
+    }
+  }
+}
+
+impl Checkpoint {
+  /// Reads the record starting at ```offset``` cells into the allocation ```base``` where the layout of the record is given by ```schema```
+  ///
+  /// Each field is checked to be inside of the allocation, initialized, and of the type in ```schema```. The values are returned in the same order as ```schema```.
+  ///
+  /// ```
+  /// use brilirs::{checkpoint::Checkpoint, record::{Field, FieldValue}};
+  /// use bril_rs::Type;
+  ///
+  /// // A checkpoint of "main" holding a single allocation with a point {x: 3, visible: true}
+  /// let checkpoint = Checkpoint::from_bytes(
+  ///   b"brilirs-checkpoint v3\ninstructions 0\nunique_ids 0\nframe main 0 0 - -\ncurrent 0 0\nenv 0\nheap 1\nblock 0 2\nint 3\nbool true\nend\n",
+  /// )
+  /// .unwrap();
+  /// let schema = [
+  ///   Field { offset: 0, field_type: Type::Int },
+  ///   Field { offset: 1, field_type: Type::Bool },
+  /// ];
+  ///
+  /// assert_eq!(
+  ///   checkpoint.read_record(0, 0, &schema).unwrap(),
+  ///   vec![FieldValue::Int(3), FieldValue::Bool(true)]
+  /// );
+  /// // The second field would be past the end of the allocation
+  /// assert!(checkpoint.read_record(0, 1, &schema).is_err());
+  /// ```
+  pub fn read_record(
+    &self,
+    base: usize,
+    offset: i64,
+    schema: &[Field],
+  ) -> Result<Vec<FieldValue>, InterpError> {
+    schema
+      .iter()
+      .enumerate()
+      .map(|(i, field)| {
+        let value = self.heap.read(&Pointer {
+          base,
+          offset: offset + field.offset,
+        })?;
+        match (value, &field.field_type) {
+          (Value::Int(v), Type::Int) => Ok(FieldValue::Int(*v)),
+          (Value::Bool(v), Type::Bool) => Ok(FieldValue::Bool(*v)),
+          (Value::Float(v), Type::Float) => Ok(FieldValue::Float(*v)),
+          (Value::Pointer(p), Type::Pointer(_)) => Ok(FieldValue::Pointer {
+            base: p.base,
+            offset: p.offset,
+          }),
+          _ => Err(InterpError::BadRecordField(i, field.field_type.clone())),
+        }
+      })
+      .collect()
+  }
+}
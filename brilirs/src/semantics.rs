@@ -0,0 +1,140 @@
+use bril_rs::ValueOps;
+
+use crate::interp::{get_arg, Environment, Value};
+
+/// The arithmetic and comparisons that the interpreter performs on values
+///
+/// Passing an implementation to [`crate::interp::execute_main_with_semantics`] routes every `add`, `sub`, `mul`, `div`, `eq`, `lt`, `gt`, `le`, `ge`, `not`, `and`, `or`, `fadd`, `fsub`, `fmul`, `fdiv`, `feq`, `flt`, `fgt`, `fle`, and `fge` through it. Each method defaults to the behaviour of the interpreter when no semantics is given so an implementation only overrides the operations that it cares about: integer arithmetic wraps on overflow, `div` truncates towards zero, and float arithmetic is IEEE 754 with rounding to nearest.
+///
+/// The values themselves are still Bril's `int`, `bool`, and `float` since they also flow through memory, calls, and `print`. An abstract domain like intervals can be modelled by an implementation that keeps its own state about the operations that it sees. When a semantics is supplied, the `arithmetic`, `rounding`, and `division` fields of [`crate::interp::InterpOptions`] have no effect on these operations.
+pub trait Semantics {
+  /// `add`
+  fn add(&mut self, a: i64, b: i64) -> i64 {
+    a.wrapping_add(b)
+  }
+  /// `sub`
+  fn sub(&mut self, a: i64, b: i64) -> i64 {
+    a.wrapping_sub(b)
+  }
+  /// `mul`
+  fn mul(&mut self, a: i64, b: i64) -> i64 {
+    a.wrapping_mul(b)
+  }
+  /// `div`. Like the built-in `div`, the default panics when ```b``` is zero
+  fn div(&mut self, a: i64, b: i64) -> i64 {
+    a.wrapping_div(b)
+  }
+  /// `eq`
+  fn eq(&mut self, a: i64, b: i64) -> bool {
+    a == b
+  }
+  /// `lt`
+  fn lt(&mut self, a: i64, b: i64) -> bool {
+    a < b
+  }
+  /// `gt`
+  fn gt(&mut self, a: i64, b: i64) -> bool {
+    a > b
+  }
+  /// `le`
+  fn le(&mut self, a: i64, b: i64) -> bool {
+    a <= b
+  }
+  /// `ge`
+  fn ge(&mut self, a: i64, b: i64) -> bool {
+    a >= b
+  }
+  /// `not`
+  fn not(&mut self, a: bool) -> bool {
+    !a
+  }
+  /// `and`
+  fn and(&mut self, a: bool, b: bool) -> bool {
+    a && b
+  }
+  /// `or`
+  fn or(&mut self, a: bool, b: bool) -> bool {
+    a || b
+  }
+  /// `fadd`
+  fn fadd(&mut self, a: f64, b: f64) -> f64 {
+    a + b
+  }
+  /// `fsub`
+  fn fsub(&mut self, a: f64, b: f64) -> f64 {
+    a - b
+  }
+  /// `fmul`
+  fn fmul(&mut self, a: f64, b: f64) -> f64 {
+    a * b
+  }
+  /// `fdiv`
+  fn fdiv(&mut self, a: f64, b: f64) -> f64 {
+    a / b
+  }
+  /// `feq`
+  fn feq(&mut self, a: f64, b: f64) -> bool {
+    a == b
+  }
+  /// `flt`
+  fn flt(&mut self, a: f64, b: f64) -> bool {
+    a < b
+  }
+  /// `fgt`
+  fn fgt(&mut self, a: f64, b: f64) -> bool {
+    a > b
+  }
+  /// `fle`
+  fn fle(&mut self, a: f64, b: f64) -> bool {
+    a <= b
+  }
+  /// `fge`
+  fn fge(&mut self, a: f64, b: f64) -> bool {
+    a >= b
+  }
+}
+
+/// The concrete semantics of Bril, using every default of [`Semantics`]
+///
+/// Useful for an implementation that wraps another one and forwards the operations that it does not change.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ConcreteSemantics;
+
+impl Semantics for ConcreteSemantics {}
+
+// Computes the result of `op` with `semantics` or returns None if `op` is not one that a semantics can override
+pub(crate) fn apply(
+  semantics: &mut dyn Semantics,
+  op: ValueOps,
+  env: &Environment,
+  args: &[usize],
+) -> Option<Value> {
+  use ValueOps::*;
+  let i = |n| get_arg::<i64>(env, n, args);
+  let b = |n| get_arg::<bool>(env, n, args);
+  let f = |n| get_arg::<f64>(env, n, args);
+  Some(match op {
+    Add => Value::Int(semantics.add(i(0), i(1))),
+    Sub => Value::Int(semantics.sub(i(0), i(1))),
+    Mul => Value::Int(semantics.mul(i(0), i(1))),
+    Div => Value::Int(semantics.div(i(0), i(1))),
+    Eq => Value::Bool(semantics.eq(i(0), i(1))),
+    Lt => Value::Bool(semantics.lt(i(0), i(1))),
+    Gt => Value::Bool(semantics.gt(i(0), i(1))),
+    Le => Value::Bool(semantics.le(i(0), i(1))),
+    Ge => Value::Bool(semantics.ge(i(0), i(1))),
+    Not => Value::Bool(semantics.not(b(0))),
+    And => Value::Bool(semantics.and(b(0), b(1))),
+    Or => Value::Bool(semantics.or(b(0), b(1))),
+    Fadd => Value::Float(semantics.fadd(f(0), f(1))),
+    Fsub => Value::Float(semantics.fsub(f(0), f(1))),
+    Fmul => Value::Float(semantics.fmul(f(0), f(1))),
+    Fdiv => Value::Float(semantics.fdiv(f(0), f(1))),
+    Feq => Value::Bool(semantics.feq(f(0), f(1))),
+    Flt => Value::Bool(semantics.flt(f(0), f(1))),
+    Fgt => Value::Bool(semantics.fgt(f(0), f(1))),
+    Fle => Value::Bool(semantics.fle(f(0), f(1))),
+    Fge => Value::Bool(semantics.fge(f(0), f(1))),
+    _ => return None,
+  })
+}
@@ -0,0 +1,53 @@
+use fxhash::FxHashMap;
+
+// The instructions that a diagnostic keeps a tally for, in the order that they were first executed. A site is identified by its function, block and index in the block
+#[derive(Debug)]
+pub struct Sites<'a, S> {
+  ids: FxHashMap<(&'a str, usize, usize), usize>,
+  sites: Vec<S>,
+}
+
+impl<S> Default for Sites<'_, S> {
+  fn default() -> Self {
+    Self {
+      ids: FxHashMap::default(),
+      sites: Vec::new(),
+    }
+  }
+}
+
+impl<'a, S> Sites<'a, S> {
+  // The index of the site of the instruction `instr` of `block` in `func`, which is made with `new` the first time that it is executed
+  pub fn id(
+    &mut self,
+    func: &'a str,
+    block: usize,
+    instr: usize,
+    new: impl FnOnce() -> S,
+  ) -> usize {
+    let next_id = self.sites.len();
+    let id = *self.ids.entry((func, block, instr)).or_insert(next_id);
+    if id == next_id {
+      self.sites.push(new());
+    }
+    id
+  }
+
+  pub fn iter(&self) -> std::slice::Iter<'_, S> {
+    self.sites.iter()
+  }
+}
+
+impl<S> std::ops::Index<usize> for Sites<'_, S> {
+  type Output = S;
+
+  fn index(&self, id: usize) -> &S {
+    &self.sites[id]
+  }
+}
+
+impl<S> std::ops::IndexMut<usize> for Sites<'_, S> {
+  fn index_mut(&mut self, id: usize) -> &mut S {
+    &mut self.sites[id]
+  }
+}
@@ -0,0 +1,191 @@
+use bril_rs::{EffectOps, Instruction, ValueOps};
+use fxhash::FxHashMap;
+
+use crate::basic_block::{BBFunction, NumifiedInstruction};
+use crate::error::InterpError;
+use crate::interp::{Environment, Pointer, Value};
+
+// The shadow state of the taint analysis. Every variable slot in the environment and every heap cell gets a taint bit stored next to it, instead of in `Value`, so that the default path of the interpreter is left alone.
+// The rules for how taint flows through each instruction are documented in the README
+#[derive(Debug, Default)]
+pub struct Taint {
+  // Parallel to `Environment::env`
+  env: Vec<bool>,
+  // Parallel to `Heap::memory`. Missing cells are untainted
+  heap: FxHashMap<usize, Vec<bool>>,
+  // The taint of the arguments of the call that is being made
+  call_args: Vec<bool>,
+  // The taint of the value that was most recently returned
+  returned: bool,
+}
+
+impl Taint {
+  fn get(&self, env: &Environment, var: usize) -> bool {
+    self
+      .env
+      .get(env.current_pointer + var)
+      .copied()
+      .unwrap_or(false)
+  }
+
+  fn set(&mut self, env: &Environment, var: usize, taint: bool) {
+    let idx = env.current_pointer + var;
+    if idx >= self.env.len() {
+      self.env.resize(env.env.len().max(idx + 1), false);
+    }
+    self.env[idx] = taint;
+  }
+
+  fn any(&self, env: &Environment, args: &[usize]) -> bool {
+    args.iter().any(|a| self.get(env, *a))
+  }
+
+  fn pointer<'a>(env: &'a Environment, var: &usize) -> &'a Pointer {
+    match env.get(var) {
+      Value::Pointer(p) => p,
+      _ => unreachable!(),
+    }
+  }
+
+  // The arguments of "main" come from outside of the program so they are the source of all taint
+  pub fn taint_inputs(&mut self, num_args: usize) {
+    self.call_args = vec![true; num_args];
+  }
+
+  // Called once the frame of `func` has been pushed. Clears anything left over in the frame from an earlier call before tainting the arguments
+  pub fn enter_function(&mut self, env: &Environment, func: &BBFunction) {
+    (0..func.num_of_vars).for_each(|v| self.set(env, v, false));
+    let call_args = std::mem::take(&mut self.call_args);
+    func
+      .args_as_nums
+      .iter()
+      .zip(call_args)
+      .for_each(|(a, t)| self.set(env, *a, t));
+  }
+
+  // Called before `instr` is executed. Checks that no tainted pointer is dereferenced and returns the taint of the result of `instr`
+  pub fn before(
+    &mut self,
+    env: &Environment,
+    instr: &Instruction,
+    numified: &NumifiedInstruction,
+    last_label: Option<&String>,
+  ) -> Result<bool, InterpError> {
+    let args = &numified.args;
+    match instr {
+      Instruction::Constant { .. } => Ok(false),
+      Instruction::Value { op, labels, .. } => match op {
+        ValueOps::Call => {
+          self.call_args = args.iter().map(|a| self.get(env, *a)).collect();
+          // The result of a native function is tainted by its arguments since it does not `ret`
+          self.returned = self.any(env, args);
+          Ok(false)
+        }
+        ValueOps::Phi => Ok(
+          last_label
+            .and_then(|l| labels.iter().position(|label| label == l))
+            .is_some_and(|i| self.get(env, args[i])),
+        ),
+        ValueOps::Alloc | ValueOps::Alloca => Ok(false),
+        ValueOps::Load => {
+          if self.get(env, args[0]) {
+            return Err(InterpError::TaintViolation(op.to_string()));
+          }
+          let p = Self::pointer(env, &args[0]);
+          Ok(
+            self
+              .heap
+              .get(&p.base)
+              .and_then(|cells| cells.get(usize::try_from(p.offset).ok()?))
+              .copied()
+              .unwrap_or(false),
+          )
+        }
+        _ => Ok(self.any(env, args)),
+      },
+      Instruction::Effect { op, .. } => {
+        match op {
+          EffectOps::Call => self.call_args = args.iter().map(|a| self.get(env, *a)).collect(),
+          EffectOps::Return => self.returned = self.any(env, args),
+          EffectOps::Store if self.get(env, args[0]) => {
+            return Err(InterpError::TaintViolation(op.to_string()))
+          }
+          _ => {}
+        }
+        Ok(false)
+      }
+    }
+  }
+
+  // Called after `instr` has been executed successfully with the taint computed by `before`
+  pub fn after(
+    &mut self,
+    env: &Environment,
+    instr: &Instruction,
+    numified: &NumifiedInstruction,
+    taint: bool,
+  ) {
+    let args = &numified.args;
+    match instr {
+      Instruction::Value {
+        op: ValueOps::Call, ..
+      } => self.set(env, numified.dest.unwrap(), self.returned),
+      Instruction::Value {
+        op: ValueOps::Dup, ..
+      } => {
+        self.set(env, numified.dest.unwrap(), taint);
+        // The copied cells keep their taint
+        let src = Self::pointer(env, &args[0]);
+        let dest = Self::pointer(env, &numified.dest.unwrap()).base;
+        let cells: Vec<bool> = self.heap.get(&src.base).map_or_else(Vec::new, |cells| {
+          cells
+            .iter()
+            .skip(src.offset as usize)
+            .take(match env.get(&args[1]) {
+              Value::Int(amount) => *amount as usize,
+              _ => unreachable!(),
+            })
+            .copied()
+            .collect()
+        });
+        self.heap.insert(dest, cells);
+      }
+      Instruction::Value {
+        op: ValueOps::Memdup,
+        ..
+      } => {
+        self.set(env, numified.dest.unwrap(), taint);
+        // The copied cells keep their taint
+        let src = Self::pointer(env, &args[0]).base;
+        let dest = Self::pointer(env, &numified.dest.unwrap()).base;
+        let cells = self.heap.get(&src).cloned().unwrap_or_default();
+        self.heap.insert(dest, cells);
+      }
+      Instruction::Constant { .. } | Instruction::Value { .. } => {
+        self.set(env, numified.dest.unwrap(), taint);
+      }
+      Instruction::Effect {
+        op: EffectOps::Store,
+        ..
+      } => {
+        let p = Self::pointer(env, &args[0]);
+        // The store succeeded so the offset is in bounds
+        let offset = p.offset as usize;
+        let taint = self.get(env, args[1]);
+        let cells = self.heap.entry(p.base).or_default();
+        if offset >= cells.len() {
+          cells.resize(offset + 1, false);
+        }
+        cells[offset] = taint;
+      }
+      Instruction::Effect {
+        op: EffectOps::Free,
+        ..
+      } => {
+        let base = Self::pointer(env, &args[0]).base;
+        self.heap.remove(&base);
+      }
+      Instruction::Effect { .. } => {}
+    }
+  }
+}
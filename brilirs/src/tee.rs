@@ -0,0 +1,53 @@
+use std::io::Write;
+
+/// A [Write] which writes everything to each of its writers in order
+///
+/// This lets the interpreter print to more than one place at once, i.e. capturing the output of a program while also showing it on [std::io::stdout].
+///
+/// ```
+/// use brilirs::{basic_block::BBProgram, interp, tee::Tee};
+/// use bril_rs::Program;
+///
+/// let src = "@main { a: int = const 4; b: bool = const true; print a b; print a; }";
+/// let prog: Program = bril2json::parse_abstract_program_from_read(src.as_bytes(), false)
+///   .try_into()
+///   .unwrap();
+/// let bbprog: BBProgram = prog.try_into().unwrap();
+///
+/// let (mut first, mut second) = (Vec::new(), Vec::new());
+/// interp::execute_main(&bbprog, Tee::new(vec![&mut first, &mut second]), &[], false, std::io::sink())
+///   .unwrap();
+///
+/// assert_eq!(first, b"4 true\n4\n");
+/// assert_eq!(first, second);
+/// ```
+#[derive(Debug)]
+pub struct Tee<W: Write> {
+  writers: Vec<W>,
+}
+
+impl<W: Write> Tee<W> {
+  /// Creates a [`Tee`] over ```writers```. Use `Box<dyn Write>` for writers of different types
+  #[must_use]
+  pub const fn new(writers: Vec<W>) -> Self {
+    Self { writers }
+  }
+
+  /// Gives back the writers
+  #[must_use]
+  pub fn into_inner(self) -> Vec<W> {
+    self.writers
+  }
+}
+
+impl<W: Write> Write for Tee<W> {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    // Every writer needs to see all of `buf` so a partial write is not an option
+    self.writers.iter_mut().try_for_each(|w| w.write_all(buf))?;
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    self.writers.iter_mut().try_for_each(Write::flush)
+  }
+}